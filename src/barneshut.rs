@@ -0,0 +1,235 @@
+use kinematics::{G, Pull, pulls_at};
+
+// how deep the tree goes before bodies at (almost) the same spot share a leaf
+const MAX_DEPTH: usize = 32;
+pub const DEFAULT_THETA: f64 = 0.5;
+// above this the opening test stops being any good
+pub const MAX_THETA: f64 = 1.;
+
+// how gravitational forces are worked out
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ForceMode {
+    // every pair of objects, exact but O(N²)
+    Direct,
+    // groups far away objects together, theta is the opening angle
+    BarnesHut { theta: f64 },
+}
+
+impl Default for ForceMode {
+    fn default() -> ForceMode {
+        ForceMode::Direct
+    }
+}
+
+impl ForceMode {
+    pub fn pulls(&self, masses: &[f64], positions: &[(f64, f64)]) -> Vec<Pull> {
+        match *self {
+            ForceMode::Direct => pulls_at(masses, positions),
+            ForceMode::BarnesHut { theta } => {
+                match QuadTree::build(masses, positions) {
+                    Some(tree) => tree.pulls(masses, positions, theta),
+                    None => vec![Pull::default(); positions.len()],
+                }
+            }
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match *self {
+            ForceMode::Direct => "direct".to_string(),
+            ForceMode::BarnesHut { theta } => format!("barnes-hut, theta {:.2}", theta),
+        }
+    }
+}
+
+struct Node {
+    center_x: f64,
+    center_y: f64,
+    half_size: f64,
+    mass: f64,
+    // mass weighted sum of the positions, divided by the mass once the tree is built
+    mass_x: f64,
+    mass_y: f64,
+    // index of the first of four children, ordered by quadrant
+    children: Option<usize>,
+    bodies: Vec<usize>,
+}
+
+impl Node {
+    fn new(center_x: f64, center_y: f64, half_size: f64) -> Node {
+        Node {
+            center_x: center_x,
+            center_y: center_y,
+            half_size: half_size,
+            mass: 0.,
+            mass_x: 0.,
+            mass_y: 0.,
+            children: None,
+            bodies: Vec::new(),
+        }
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        (x - self.center_x).abs() <= self.half_size && (y - self.center_y).abs() <= self.half_size
+    }
+
+    fn quadrant(&self, x: f64, y: f64) -> usize {
+        match (x >= self.center_x, y >= self.center_y) {
+            (false, false) => 0,
+            (true, false) => 1,
+            (false, true) => 2,
+            (true, true) => 3,
+        }
+    }
+}
+
+pub struct QuadTree {
+    nodes: Vec<Node>,
+}
+
+impl QuadTree {
+    // None if there's nothing to put in it
+    pub fn build(masses: &[f64], positions: &[(f64, f64)]) -> Option<QuadTree> {
+        if positions.is_empty() {
+            return None;
+        }
+        let (mut min_x, mut min_y) = positions[0];
+        let (mut max_x, mut max_y) = positions[0];
+        for &(x, y) in positions {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        let half_size = ((max_x - min_x).max(max_y - min_y) / 2.).max(1.);
+        let mut tree = QuadTree {
+            nodes: vec![Node::new((min_x + max_x) / 2., (min_y + max_y) / 2., half_size)],
+        };
+        for i in 0..positions.len() {
+            tree.insert(0, i, masses, positions, 0);
+        }
+        for node in tree.nodes.iter_mut() {
+            if node.mass > 0. {
+                node.mass_x /= node.mass;
+                node.mass_y /= node.mass;
+            }
+        }
+        Some(tree)
+    }
+
+    fn insert(&mut self,
+              node: usize,
+              body: usize,
+              masses: &[f64],
+              positions: &[(f64, f64)],
+              depth: usize) {
+        let (x, y) = positions[body];
+        {
+            let ref mut n = self.nodes[node];
+            n.mass += masses[body];
+            n.mass_x += masses[body] * x;
+            n.mass_y += masses[body] * y;
+        }
+        if let Some(first) = self.nodes[node].children {
+            let child = first + self.nodes[node].quadrant(x, y);
+            self.insert(child, body, masses, positions, depth + 1);
+            return;
+        }
+        if self.nodes[node].bodies.is_empty() || depth == MAX_DEPTH {
+            self.nodes[node].bodies.push(body);
+            return;
+        }
+        // split the leaf and push its body down along with the new one
+        let first = self.nodes.len();
+        let (cx, cy, half) = (self.nodes[node].center_x,
+                              self.nodes[node].center_y,
+                              self.nodes[node].half_size / 2.);
+        self.nodes.push(Node::new(cx - half, cy - half, half));
+        self.nodes.push(Node::new(cx + half, cy - half, half));
+        self.nodes.push(Node::new(cx - half, cy + half, half));
+        self.nodes.push(Node::new(cx + half, cy + half, half));
+        self.nodes[node].children = Some(first);
+        let old_bodies = ::std::mem::replace(&mut self.nodes[node].bodies, Vec::new());
+        for old in old_bodies.into_iter().chain(Some(body)) {
+            let (ox, oy) = positions[old];
+            let child = first + self.nodes[node].quadrant(ox, oy);
+            self.insert(child, old, masses, positions, depth + 1);
+        }
+    }
+
+    pub fn pulls(&self, masses: &[f64], positions: &[(f64, f64)], theta: f64) -> Vec<Pull> {
+        (0..positions.len())
+            .map(|i| self.pull_on(i, masses, positions, theta))
+            .collect()
+    }
+
+    // only bodies worked out one by one are considered for the closest one, cells
+    // treated as a single mass are far enough away not to matter
+    pub fn pull_on(&self,
+                   body: usize,
+                   masses: &[f64],
+                   positions: &[(f64, f64)],
+                   theta: f64)
+                   -> Pull {
+        let (x, y) = positions[body];
+        let mut total = Pull::default();
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let ref node = self.nodes[index];
+            if node.mass <= 0. {
+                continue;
+            }
+            match node.children {
+                None => {
+                    for &other in node.bodies.iter().filter(|&&other| other != body) {
+                        let distance_cubed =
+                            pull(&mut total, x, y, masses[other], positions[other]);
+                        if let Some(distance_cubed) = distance_cubed {
+                            total.consider(other,
+                                           distance_cubed,
+                                           masses[body] + masses[other]);
+                        }
+                    }
+                }
+                Some(first) => {
+                    let (dx, dy) = (node.mass_x - x, node.mass_y - y);
+                    let distance = (dx * dx + dy * dy).sqrt();
+                    // far enough away to treat as a single mass, which a cell the body
+                    // is in never is since its own mass would pull on it
+                    if distance > 0. && 2. * node.half_size / distance < theta &&
+                       !node.contains(x, y) {
+                        pull(&mut total, x, y, node.mass, (node.mass_x, node.mass_y));
+                    } else {
+                        stack.extend(first..first + 4);
+                    }
+                }
+            }
+        }
+        total
+    }
+
+    // center and half size of every cell that has something in it, for drawing
+    pub fn cells(&self) -> Vec<(f64, f64, f64)> {
+        self.nodes
+            .iter()
+            .filter(|n| n.mass > 0.)
+            .map(|n| (n.center_x, n.center_y, n.half_size))
+            .collect()
+    }
+}
+
+// adds the pull of a mass at a point to the acceleration of something at x, y,
+// returning the distance cubed unless they're at the same place
+fn pull(pull: &mut Pull, x: f64, y: f64, mass: f64, at: (f64, f64)) -> Option<f64> {
+    let (dx, dy) = (at.0 - x, at.1 - y);
+    let distance_squared = dx * dx + dy * dy;
+    if distance_squared > 0. {
+        let distance_cubed = distance_squared * distance_squared.sqrt();
+        let factor = G * mass / distance_cubed;
+        pull.acceleration.0 += factor * dx;
+        pull.acceleration.1 += factor * dy;
+        Some(distance_cubed)
+    } else {
+        None
+    }
+}
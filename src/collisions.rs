@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+// objects covering more cells than this are compared against every other object
+// instead of being put in the grid
+const MAX_CELLS_PER_OBJECT: i64 = 64;
+
+// groups of objects that touch each other, directly or through other objects in the
+// group, each sorted with the lowest index first
+// objects are bucketed into a grid of cells about as big as a typical object, so only
+// objects sharing a cell are compared
+pub fn touching_groups(positions: &[(f64, f64)], radii: &[f64]) -> Vec<Vec<usize>> {
+    let pairs = touching_pairs(positions, radii);
+    if pairs.is_empty() {
+        return Vec::new();
+    }
+    let mut parents: Vec<usize> = (0..positions.len()).collect();
+    for (i, j) in pairs {
+        let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+        // the lower index ends up as the root, so it's what everything merges into
+        if a < b {
+            parents[b] = a;
+        } else if b < a {
+            parents[a] = b;
+        }
+    }
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..positions.len() {
+        let r = root(&mut parents, i);
+        if r != i {
+            groups.entry(r).or_insert_with(|| vec![r]).push(i);
+        }
+    }
+    let mut groups: Vec<Vec<usize>> = groups.into_iter().map(|(_, group)| group).collect();
+    groups.sort_by_key(|group| group[0]);
+    groups
+}
+
+fn root(parents: &mut [usize], i: usize) -> usize {
+    let mut r = i;
+    while parents[r] != r {
+        r = parents[r];
+    }
+    // point everything on the way straight at the root
+    let mut i = i;
+    while parents[i] != r {
+        let next = parents[i];
+        parents[i] = r;
+        i = next;
+    }
+    r
+}
+
+fn touches(positions: &[(f64, f64)], radii: &[f64], i: usize, j: usize) -> bool {
+    let (dx, dy) = (positions[j].0 - positions[i].0, positions[j].1 - positions[i].1);
+    (dx * dx + dy * dy).sqrt() < radii[i] + radii[j]
+}
+
+// every touching pair once, lower index first
+fn touching_pairs(positions: &[(f64, f64)], radii: &[f64]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    if positions.len() < 2 {
+        return pairs;
+    }
+    let mean_radius = radii.iter().sum::<f64>() / radii.len() as f64;
+    let cell_size = 2. * mean_radius;
+    if !(cell_size > 0.) {
+        return pairs;
+    }
+    let cell_of = |x: f64| (x / cell_size).floor() as i64;
+    let mut cells: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    let mut large = Vec::new();
+    for (i, (&(x, y), &r)) in positions.iter().zip(radii.iter()).enumerate() {
+        let (min_x, min_y) = (cell_of(x - r), cell_of(y - r));
+        let (max_x, max_y) = (cell_of(x + r), cell_of(y + r));
+        if (max_x - min_x + 1) * (max_y - min_y + 1) > MAX_CELLS_PER_OBJECT {
+            large.push(i);
+            continue;
+        }
+        for cx in min_x..max_x + 1 {
+            for cy in min_y..max_y + 1 {
+                cells.entry((cx, cy)).or_insert_with(Vec::new).push(i);
+            }
+        }
+    }
+    for (&cell, objects) in &cells {
+        for (n, &i) in objects.iter().enumerate() {
+            for &j in &objects[n + 1..] {
+                // a pair can share several cells, it's only checked in the one holding
+                // the corner of where their boxes overlap
+                let corner = ((positions[i].0 - radii[i]).max(positions[j].0 - radii[j]),
+                              (positions[i].1 - radii[i]).max(positions[j].1 - radii[j]));
+                if (cell_of(corner.0), cell_of(corner.1)) == cell &&
+                   touches(positions, radii, i, j) {
+                    pairs.push((i.min(j), i.max(j)));
+                }
+            }
+        }
+    }
+    for (n, &i) in large.iter().enumerate() {
+        for j in 0..positions.len() {
+            // pairs of large objects only once
+            let other_large_before = large[..n].contains(&j);
+            if j != i && !other_large_before && touches(positions, radii, i, j) {
+                pairs.push((i.min(j), i.max(j)));
+            }
+        }
+    }
+    pairs
+}
@@ -1,3 +1,7 @@
+use physics_sim::Object;
+use coloruniverse::ColorUniverse;
+use kinematics::velocity;
+
 #[macro_export]
 macro_rules! color_func {
     ($this:expr, $func:ident, $color:expr) => {
@@ -5,13 +9,52 @@ macro_rules! color_func {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Color(pub f64, pub f64, pub f64);
 
-#[derive(Clone)]
+// objects keep their user set color, any computed one is drawn with the mode chosen
+// for the view
+#[derive(Clone, PartialEq)]
 pub enum ObjectColor {
     UserSet(Color),
+    // log scale
     FromMass,
+    FromSpeed,
+    // log scale
+    FromKineticEnergy,
+    FromBarycenterDistance,
+}
+
+impl ObjectColor {
+    pub fn resolve(&self, object: &Object, context: &ColorContext) -> Color {
+        match *self {
+            ObjectColor::UserSet(ref c) => c.clone(),
+            _ => {
+                let value = context.value(&context.mode, object);
+                context.palette.color(context.range(&context.mode).normalize(value))
+            }
+        }
+    }
+
+    // the next computed mode, for cycling through them
+    pub fn next_mode(&self) -> ObjectColor {
+        match *self {
+            ObjectColor::FromMass => ObjectColor::FromSpeed,
+            ObjectColor::FromSpeed => ObjectColor::FromKineticEnergy,
+            ObjectColor::FromKineticEnergy => ObjectColor::FromBarycenterDistance,
+            _ => ObjectColor::FromMass,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ObjectColor::UserSet(_) => "user set",
+            ObjectColor::FromMass => "mass",
+            ObjectColor::FromSpeed => "speed",
+            ObjectColor::FromKineticEnergy => "kinetic energy",
+            ObjectColor::FromBarycenterDistance => "distance from barycenter",
+        }
+    }
 }
 
 impl Into<ObjectColor> for Color {
@@ -20,37 +63,193 @@ impl Into<ObjectColor> for Color {
     }
 }
 
-const COLOR_MOD: usize = 300_000_000;
-const COLOR_SEGMENT: usize = COLOR_MOD / 6;
-pub fn mass_to_color(mass: f64) -> Color {
-    let mmass = mass as usize % COLOR_MOD;
-    let (r, b, g);
-    // setup blue
-    if mmass < COLOR_SEGMENT {
-        r = 255;
-        g = 51 + (255 - 51) * (mmass / COLOR_SEGMENT);
-        b = 51;
-    } else if mmass >= COLOR_SEGMENT && mmass < 2 * COLOR_SEGMENT {
-        r = 255 - (255 - 51) * ((mmass - COLOR_SEGMENT) / COLOR_SEGMENT);
-        g = 255;
-        b = 51;
-    } else if mmass >= COLOR_SEGMENT * 2 && mmass < 3 * COLOR_SEGMENT {
-        r = 51;
-        g = 255;
-        b = 51 + (255 - 51) * ((mmass - COLOR_SEGMENT * 2) / COLOR_SEGMENT);
-    } else if mmass >= COLOR_SEGMENT * 3 && mmass < 4 * COLOR_SEGMENT {
-        r = 51;
-        g = 255 - (255 - 51) * ((mmass - COLOR_SEGMENT * 3) / COLOR_SEGMENT);
-        b = 255;
-    } else if mmass >= COLOR_SEGMENT * 4 && mmass < 5 * COLOR_SEGMENT {
-        r = 51 + (255 - 51) * ((mmass - COLOR_SEGMENT * 4) / COLOR_SEGMENT);
-        g = 51;
-        b = 255;
-    } else {
-        r = 255;
-        g = 51;
-        b = 255 - (255 - 51) * ((mmass - COLOR_SEGMENT * 5) / COLOR_SEGMENT);
-    }
-
-    Color(r as f64 / 255., g as f64 / 255., b as f64 / 255.)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Palette {
+    // the original red -> yellow -> green -> cyan -> blue -> magenta colors, stopping
+    // before it comes back around to red so both ends can be told apart
+    Classic,
+    Viridis,
+    Plasma,
+    HueWheel,
+}
+
+const CLASSIC_STOPS: [Color; 6] = [Color(1.0, 0.2, 0.2),
+                                   Color(1.0, 1.0, 0.2),
+                                   Color(0.2, 1.0, 0.2),
+                                   Color(0.2, 1.0, 1.0),
+                                   Color(0.2, 0.2, 1.0),
+                                   Color(1.0, 0.2, 1.0)];
+const VIRIDIS_STOPS: [Color; 5] = [Color(0.267, 0.004, 0.329),
+                                   Color(0.231, 0.322, 0.545),
+                                   Color(0.129, 0.569, 0.549),
+                                   Color(0.369, 0.788, 0.384),
+                                   Color(0.992, 0.906, 0.145)];
+const PLASMA_STOPS: [Color; 5] = [Color(0.051, 0.031, 0.529),
+                                  Color(0.494, 0.012, 0.659),
+                                  Color(0.8, 0.278, 0.471),
+                                  Color(0.973, 0.584, 0.251),
+                                  Color(0.941, 0.976, 0.129)];
+// stops short of going all the way around, so both ends can be told apart
+const HUE_WHEEL_RANGE: f64 = 300.;
+
+impl Palette {
+    // t goes from 0 to 1
+    pub fn color(&self, t: f64) -> Color {
+        match *self {
+            Palette::Classic => interpolate(&CLASSIC_STOPS, t),
+            Palette::Viridis => interpolate(&VIRIDIS_STOPS, t),
+            Palette::Plasma => interpolate(&PLASMA_STOPS, t),
+            Palette::HueWheel => hue_to_color(t.max(0.).min(1.) * HUE_WHEEL_RANGE),
+        }
+    }
+
+    pub fn next(&self) -> Palette {
+        match *self {
+            Palette::Classic => Palette::Viridis,
+            Palette::Viridis => Palette::Plasma,
+            Palette::Plasma => Palette::HueWheel,
+            Palette::HueWheel => Palette::Classic,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Palette::Classic => "classic",
+            Palette::Viridis => "viridis",
+            Palette::Plasma => "plasma",
+            Palette::HueWheel => "hue wheel",
+        }
+    }
+}
+
+// fully saturated color for a hue in degrees
+fn hue_to_color(hue: f64) -> Color {
+    let h = (hue % 360.) / 60.;
+    let x = 1. - (h % 2. - 1.).abs();
+    match h as usize {
+        0 => Color(1., x, 0.),
+        1 => Color(x, 1., 0.),
+        2 => Color(0., 1., x),
+        3 => Color(0., x, 1.),
+        4 => Color(x, 0., 1.),
+        _ => Color(1., 0., x),
+    }
+}
+
+// linearly interpolates between evenly spaced color stops, t goes from 0 to 1
+pub fn interpolate(stops: &[Color], t: f64) -> Color {
+    let t = t.max(0.).min(1.) * (stops.len() - 1) as f64;
+    let i = (t.floor() as usize).min(stops.len() - 2);
+    let f = t - i as f64;
+    let (a, b) = (&stops[i], &stops[i + 1]);
+    Color(a.0 + (b.0 - a.0) * f, a.1 + (b.1 - a.1) * f, a.2 + (b.2 - a.2) * f)
+}
+
+// dark to bright, used for the field heatmap
+pub const HEATMAP_STOPS: [Color; 5] = [Color(0.0, 0.0, 0.05),
+                                       Color(0.25, 0.05, 0.4),
+                                       Color(0.65, 0.15, 0.4),
+                                       Color(0.95, 0.45, 0.1),
+                                       Color(1.0, 0.95, 0.6)];
+
+#[derive(Clone, Debug)]
+pub struct ValueRange {
+    pub min: f64,
+    pub max: f64,
+    pub log: bool,
+}
+
+impl ValueRange {
+    fn from_values<I: Iterator<Item = f64>>(values: I, log: bool) -> ValueRange {
+        let mut range = ValueRange {
+            min: ::std::f64::INFINITY,
+            max: ::std::f64::NEG_INFINITY,
+            log: log,
+        };
+        for value in values.filter(|&v| !log || v > 0.) {
+            range.min = range.min.min(value);
+            range.max = range.max.max(value);
+        }
+        range
+    }
+
+    // where the value falls in the range, from 0 to 1
+    pub fn normalize(&self, value: f64) -> f64 {
+        let (value, min, max) = if self.log {
+            (value.max(::std::f64::MIN_POSITIVE).ln(), self.min.ln(), self.max.ln())
+        } else {
+            (value, self.min, self.max)
+        };
+        if max > min {
+            ((value - min) / (max - min)).max(0.).min(1.)
+        } else {
+            0.5
+        }
+    }
+}
+
+// everything needed to turn computed object colors into actual colors, worked out once
+// per frame from the whole universe
+pub struct ColorContext {
+    pub palette: Palette,
+    pub mode: ObjectColor,
+    barycenter: (f64, f64),
+    mass: ValueRange,
+    speed: ValueRange,
+    kinetic_energy: ValueRange,
+    barycenter_distance: ValueRange,
+}
+
+impl ColorContext {
+    pub fn new(universe: &ColorUniverse, palette: Palette, mode: ObjectColor) -> ColorContext {
+        let objects = universe.objects();
+        let barycenter = universe.barycenter().map(|p| (p.x, p.y)).unwrap_or((0., 0.));
+        let distances = objects.iter().map(|o| distance_to(o, barycenter));
+        ColorContext {
+            palette: palette,
+            mode: mode,
+            barycenter: barycenter,
+            mass: ValueRange::from_values(objects.iter().map(|o| o.mass()), true),
+            speed: ValueRange::from_values(objects.iter().map(speed), false),
+            kinetic_energy: ValueRange::from_values(objects.iter().map(kinetic_energy), true),
+            barycenter_distance: ValueRange::from_values(distances, false),
+        }
+    }
+
+    pub fn value(&self, mode: &ObjectColor, object: &Object) -> f64 {
+        match *mode {
+            ObjectColor::UserSet(_) => 0.,
+            ObjectColor::FromMass => object.mass(),
+            ObjectColor::FromSpeed => speed(object),
+            ObjectColor::FromKineticEnergy => kinetic_energy(object),
+            ObjectColor::FromBarycenterDistance => distance_to(object, self.barycenter),
+        }
+    }
+
+    pub fn range(&self, mode: &ObjectColor) -> &ValueRange {
+        match *mode {
+            ObjectColor::FromSpeed => &self.speed,
+            ObjectColor::FromKineticEnergy => &self.kinetic_energy,
+            ObjectColor::FromBarycenterDistance => &self.barycenter_distance,
+            _ => &self.mass,
+        }
+    }
+
+    // color for an object that isn't in the universe yet, like the one being placed
+    pub fn mass_color(&self, mass: f64) -> Color {
+        self.palette.color(self.mass.normalize(mass))
+    }
+}
+
+fn speed(object: &Object) -> f64 {
+    let (vx, vy) = velocity(object);
+    (vx * vx + vy * vy).sqrt()
+}
+
+fn distance_to(object: &Object, (x, y): (f64, f64)) -> f64 {
+    ((object.position().x - x).powi(2) + (object.position().y - y).powi(2)).sqrt()
+}
+
+fn kinetic_energy(object: &Object) -> f64 {
+    0.5 * object.mass() * speed(object).powi(2)
 }
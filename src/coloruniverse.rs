@@ -1,17 +1,34 @@
 use physics_sim::{Universe, Object, Point};
 use super::color::ObjectColor;
-use std::collections::VecDeque;
-use std::ops::Index;
-
-const MAX_POSITIONS_STORED: usize = 100000;
-const ADD_POSITION_MULTIPLE: usize = 2;
+use draw::Anchor;
+use kinematics::{velocity, vector_from_components, shortest_time_scale};
+use integrator::{Integrator, State};
+use forces::Forces;
+use updater::AdaptiveTimestep;
+use trails::{SharedTrails, Trails, TrailSettings, TrailLimit, CapVecDeque};
+use std::sync::RwLockReadGuard;
 
+// cloning shares the trails, so copies sent between threads only carry object state
 #[derive(Clone)]
 pub struct ColorUniverse {
     universe: Universe,
     colors: Vec<ObjectColor>,
-    previous_positions: Vec<CapVecDeque>,
+    // stable across merges, unlike indices
+    ids: Vec<usize>,
+    names: Vec<Option<String>>,
+    next_id: usize,
+    trails: SharedTrails,
     update_counter: usize,
+    // simulated time since the universe was created
+    elapsed: f64,
+    // simulated time stepped through in either direction, trail samples are stamped
+    // with it so they stay in order when running backwards
+    clock: f64,
+    // length of each iteration in the last step
+    last_dt: f64,
+    // picked up from our own integrators' force evaluations, None when it has to be
+    // worked out from scratch
+    time_scale: Option<f64>,
 }
 
 impl Default for ColorUniverse {
@@ -19,8 +36,15 @@ impl Default for ColorUniverse {
         ColorUniverse {
             universe: Universe::default(),
             colors: Vec::new(),
-            previous_positions: Vec::new(),
+            ids: Vec::new(),
+            names: Vec::new(),
+            next_id: 0,
+            trails: SharedTrails::default(),
             update_counter: 0,
+            elapsed: 0.,
+            clock: 0.,
+            last_dt: 0.,
+            time_scale: None,
         }
     }
 }
@@ -30,107 +54,391 @@ impl ColorUniverse {
         self.universe.objects()
     }
 
-    pub fn object_mapped<'a>
-        (&'a self)
-         -> impl Iterator<Item = (&'a Object, &'a ObjectColor, &'a CapVecDeque)> {
+    // objects along with their colors and ids
+    pub fn object_mapped<'a>(&'a self)
+                             -> impl Iterator<Item = (&'a Object, &'a ObjectColor, usize)> {
         self.universe
             .objects()
             .iter()
             .zip(self.colors.iter())
-            .zip(self.previous_positions.iter())
-            .map(|((o, c), v)| (o, c, v))
+            .zip(self.ids.iter())
+            .map(|((o, c), &id)| (o, c, id))
     }
 
-    pub fn add_object(&mut self, object: Object, color: ObjectColor) {
-        self.universe.add_object(object);
-        self.colors.push(color);
-        self.previous_positions.push(CapVecDeque::with_capacity(MAX_POSITIONS_STORED));
+    pub fn trails(&self) -> RwLockReadGuard<Trails> {
+        self.trails.get_trails()
     }
 
-    pub fn update_state_repeat(&mut self, time: f64, iterations: usize) {
-        for index in self.universe.update_state_repeat(time, iterations) {
-            self.colors.remove(index);
-            self.previous_positions.remove(index);
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    pub fn clock(&self) -> f64 {
+        self.clock
+    }
+
+    pub fn last_dt(&self) -> f64 {
+        self.last_dt
+    }
+
+    // shortest time scale any pair of objects changes over, see shortest_time_scale
+    // the library integrator doesn't share its forces, so it goes through every pair
+    pub fn time_scale(&self) -> Option<f64> {
+        self.time_scale.or_else(|| shortest_time_scale(self.objects()))
+    }
+
+    pub fn steps(&self) -> usize {
+        self.update_counter
+    }
+
+    pub fn total_mass(&self) -> f64 {
+        self.objects().iter().map(|o| o.mass()).sum()
+    }
+
+    pub fn barycenter(&self) -> Option<Point> {
+        let total_mass = self.total_mass();
+        if total_mass <= 0. {
+            return None;
         }
-        self.update_counter += 1;
-        if self.update_counter % ADD_POSITION_MULTIPLE == 0 {
-            for i in 0..self.objects().len() {
-                let pt = self.objects()[i].position().clone();
-                self.previous_positions[i].add_element(pt);
+        let (mut x, mut y) = (0., 0.);
+        for object in self.objects() {
+            x += object.mass() * object.position().x;
+            y += object.mass() * object.position().y;
+        }
+        Some(Point::new(x / total_mass, y / total_mass))
+    }
+
+    pub fn barycentric_velocity(&self) -> Option<(f64, f64)> {
+        let total_mass = self.total_mass();
+        if total_mass <= 0. {
+            return None;
+        }
+        let (mut vx, mut vy) = (0., 0.);
+        for object in self.objects() {
+            let (ovx, ovy) = velocity(object);
+            vx += object.mass() * ovx;
+            vy += object.mass() * ovy;
+        }
+        Some((vx / total_mass, vy / total_mass))
+    }
+
+    // subtracts the barycentric velocity from every object so the system stops drifting
+    pub fn zero_momentum(&mut self) {
+        if let Some((bvx, bvy)) = self.barycentric_velocity() {
+            self.rebuild(|object| {
+                let (vx, vy) = velocity(object);
+                Object::new(object.mass(),
+                            vector_from_components(vx - bvx, vy - bvy),
+                            object.position().clone())
+            });
+        }
+    }
+
+    // replaces every object with a modified copy, keeping colors and paths
+    fn rebuild<F: Fn(&Object) -> Object>(&mut self, f: F) {
+        let mut universe = Universe::default();
+        for object in self.universe.objects() {
+            universe.add_object(f(object));
+        }
+        self.universe = universe;
+        self.time_scale = None;
+    }
+
+    // steps with one of our own integrators instead of physics_sim's,
+    // returning the merged indices like physics_sim does
+    fn integrate(&mut self,
+                 time: f64,
+                 iterations: usize,
+                 integrator: Integrator,
+                 forces: &Forces)
+                 -> Vec<usize> {
+        let mut state = State::from_objects(self.objects());
+        let mut removed = Vec::new();
+        let dt = time / iterations as f64;
+        for _ in 0..iterations {
+            state.step(integrator, forces, dt);
+            removed.extend(state.merge_collisions());
+        }
+        self.set_state(&state);
+        removed
+    }
+
+    // like integrate, but every iteration is as long as the time scale the force
+    // evaluation before it found allows, returning the number of iterations too
+    fn integrate_adaptive(&mut self,
+                          time: f64,
+                          fixed_dt: f64,
+                          adaptive: &AdaptiveTimestep,
+                          integrator: Integrator,
+                          forces: &Forces)
+                          -> (Vec<usize>, usize) {
+        let mut state = State::from_objects(self.objects());
+        let mut removed = Vec::new();
+        let (mut remaining, mut iterations) = (time.abs(), 0);
+        // the first iteration goes by what the last step ended with
+        let mut time_scale = self.time_scale();
+        while remaining > 0. {
+            // the last iteration takes exactly what's left
+            let dt = adaptive.dt(fixed_dt, time_scale).min(remaining);
+            state.step(integrator, forces, dt * time.signum());
+            removed.extend(state.merge_collisions());
+            remaining -= dt;
+            iterations += 1;
+            time_scale = state.time_scale;
+        }
+        self.set_state(&state);
+        (removed, iterations)
+    }
+
+    fn set_state(&mut self, state: &State) {
+        self.time_scale = state.time_scale;
+        let mut universe = Universe::default();
+        for object in state.to_objects() {
+            universe.add_object(object);
+        }
+        self.universe = universe;
+    }
+
+    pub fn anchor_position(&self, anchor: &Anchor) -> Option<Point> {
+        match *anchor {
+            Anchor::Barycenter => self.barycenter(),
+            Anchor::Object(id) => {
+                self.index_of(id).map(|index| self.objects()[index].position().clone())
             }
         }
     }
-}
 
-trait CircularBuffer {
-    fn add_element(&mut self, element: Point);
-}
+    pub fn anchor_velocity(&self, anchor: &Anchor) -> Option<(f64, f64)> {
+        match *anchor {
+            Anchor::Barycenter => self.barycentric_velocity(),
+            Anchor::Object(id) => self.index_of(id).map(|index| velocity(&self.objects()[index])),
+        }
+    }
 
-impl CircularBuffer for CapVecDeque {
-    fn add_element(&mut self, element: Point) {
-        if self.len() == self.capacity() {
-            self.pop_front();
-            self.push_back(element);
-        } else {
-            self.push_back(element);
+    pub fn anchor_history<'a>(&self,
+                              trails: &'a Trails,
+                              anchor: &Anchor)
+                              -> Option<&'a CapVecDeque> {
+        match *anchor {
+            Anchor::Barycenter => Some(trails.barycenter()),
+            Anchor::Object(id) => trails.path(id),
         }
     }
-}
 
-// ugly as hell workaround because normal vecdeque does not preserve capacity on clone
-pub struct CapVecDeque {
-    capacity: usize,
-    inner: VecDeque<Point>,
-}
+    // leaving out the object at except
+    pub fn most_massive(&self, except: Option<usize>) -> Option<usize> {
+        let mut best: Option<(usize, f64)> = None;
+        for (i, object) in self.objects().iter().enumerate() {
+            if except == Some(i) {
+                continue;
+            }
+            match best {
+                Some((_, mass)) if mass >= object.mass() => {}
+                _ => best = Some((i, object.mass())),
+            }
+        }
+        best.map(|(i, _)| i)
+    }
 
-impl CapVecDeque {
-    pub fn new() -> CapVecDeque {
-        CapVecDeque {
-            capacity: 1,
-            inner: VecDeque::new(),
+    // the closest object whose edge is within tolerance of the point
+    pub fn object_at(&self, point: &Point, tolerance: f64) -> Option<usize> {
+        let mut best: Option<(usize, f64)> = None;
+        for (i, object) in self.objects().iter().enumerate() {
+            let edge_distance = point.distance_to(object.position()) - object.radius();
+            if edge_distance <= tolerance {
+                match best {
+                    Some((_, distance)) if distance <= edge_distance => {}
+                    _ => best = Some((i, edge_distance)),
+                }
+            }
         }
+        best.map(|(i, _)| i)
     }
 
-    pub fn with_capacity(cap: usize) -> CapVecDeque {
-        CapVecDeque {
-            capacity: cap,
-            inner: VecDeque::with_capacity(cap),
+    // returns the new object's id
+    pub fn add_object(&mut self, object: Object, color: ObjectColor) -> usize {
+        self.universe.add_object(object);
+        self.time_scale = None;
+        self.colors.push(color);
+        let id = self.next_id;
+        self.next_id += 1;
+        self.ids.push(id);
+        self.names.push(None);
+        self.trails.get_trails_mut().add_path(id);
+        id
+    }
+
+    // trail settings live in the shared trails, so every copy of the universe sees changes
+    pub fn trail_settings(&self) -> TrailSettings {
+        self.trails().settings().clone()
+    }
+
+    pub fn set_trail_settings(&self, settings: TrailSettings) {
+        self.trails.get_trails_mut().set_settings(settings);
+    }
+
+    pub fn trail_limit(&self, index: usize) -> TrailLimit {
+        self.trails().limit(self.ids[index])
+    }
+
+    // None goes back to the global limit
+    pub fn set_trail_limit(&self, id: usize, limit: Option<TrailLimit>) {
+        self.trails.get_trails_mut().set_limit(id, limit);
+    }
+
+    // forgets trail samples from after this state, so it can replace a newer one
+    pub fn rewind_trails(&self) {
+        self.trails.get_trails_mut().truncate_after(self.clock);
+    }
+
+    pub fn id_of(&self, index: usize) -> Option<usize> {
+        self.ids.get(index).cloned()
+    }
+
+    pub fn index_of(&self, id: usize) -> Option<usize> {
+        self.ids.iter().position(|&i| i == id)
+    }
+
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.names.get(index).and_then(|n| n.as_ref()).map(|n| n.as_str())
+    }
+
+    // id of the first object with the name
+    pub fn id_named(&self, name: &str) -> Option<usize> {
+        self.names
+            .iter()
+            .position(|n| n.as_ref().map_or(false, |n| n == name))
+            .map(|index| self.ids[index])
+    }
+
+    // the name if there is one, otherwise the id
+    pub fn label(&self, index: usize) -> String {
+        match self.name(index) {
+            Some(name) => name.to_string(),
+            None => format!("#{}", self.ids[index]),
         }
     }
 
-    pub fn len(&self) -> usize {
-        self.inner.len()
+    // an empty name removes it
+    pub fn set_name(&mut self, id: usize, name: String) {
+        if let Some(index) = self.index_of(id) {
+            self.names[index] = if name.is_empty() { None } else { Some(name) };
+        }
     }
 
-    pub fn capacity(&self) -> usize {
-        self.capacity
+    // the library integrator always uses physics_sim's own forces
+    pub fn update_state_repeat(&mut self,
+                               time: f64,
+                               iterations: usize,
+                               integrator: Integrator,
+                               forces: &Forces) {
+        let removed = match integrator {
+            Integrator::Library => {
+                self.time_scale = None;
+                self.universe.update_state_repeat(time, iterations)
+            }
+            _ => self.integrate(time, iterations, integrator, forces),
+        };
+        self.finish_step(time, iterations, removed);
     }
 
-    pub fn pop_front(&mut self) -> Option<Point> {
-        self.inner.pop_front()
+    // like update_state_repeat, but splits the step up as finely as the closest
+    // encounter needs, iterations is what it would be split into otherwise
+    pub fn update_state_adaptive(&mut self,
+                                 time: f64,
+                                 iterations: usize,
+                                 adaptive: &AdaptiveTimestep,
+                                 integrator: Integrator,
+                                 forces: &Forces) {
+        let fixed_dt = time.abs() / iterations as f64;
+        let (removed, iterations) = match integrator {
+            // physics_sim has to be told the number of iterations up front
+            Integrator::Library => {
+                let dt = adaptive.dt(fixed_dt, self.time_scale());
+                let iterations = (time.abs() / dt).ceil().max(1.) as usize;
+                self.time_scale = None;
+                (self.universe.update_state_repeat(time, iterations), iterations)
+            }
+            _ => self.integrate_adaptive(time, fixed_dt, adaptive, integrator, forces),
+        };
+        self.finish_step(time, iterations, removed);
     }
 
-    pub fn push_back(&mut self, elem: Point) {
-        self.inner.push_back(elem)
+    // bookkeeping after the objects have been stepped
+    fn finish_step(&mut self, time: f64, iterations: usize, removed: Vec<usize>) {
+        for index in removed {
+            self.colors.remove(index);
+            let id = self.ids.remove(index);
+            self.names.remove(index);
+            self.trails.get_trails_mut().remove_path(id);
+        }
+        self.update_counter += 1;
+        self.elapsed += time;
+        self.clock += time.abs();
+        self.last_dt = time.abs() / iterations.max(1) as f64;
+        let sample_interval = self.trails().settings().sample_interval.max(1);
+        if self.update_counter % sample_interval == 0 {
+            let positions: Vec<(usize, Point)> = self.ids
+                .iter()
+                .cloned()
+                .zip(self.objects().iter().map(|o| o.position().clone()))
+                .collect();
+            let barycenter = self.barycenter();
+            self.trails.get_trails_mut().sample(self.clock, &positions, barycenter);
+        }
     }
 }
 
-impl Index<usize> for CapVecDeque {
-    type Output = Point;
-    fn index(&self, index: usize) -> &Point {
-        &self.inner[index]
+#[cfg(test)]
+mod tests {
+    use super::ColorUniverse;
+    use physics_sim::{Object, Point, Vector};
+    use color::ObjectColor;
+    use integrator::Integrator;
+    use barneshut::ForceMode;
+    use forces::Forces;
+    use kinematics::{G, vector_from_components};
+    use std::f64::consts::PI;
+
+    // a small moon a twentieth of the way around a circular orbit, split into enough
+    // iterations that how each integrator steps hardly matters
+    fn moon_after_step(integrator: Integrator) -> (f64, f64, f64) {
+        let (mass, moon_mass) = (1e16, 1e6);
+        let size = Object::new(mass, Vector::default(), Point::new(0., 0.)).radius() +
+                   Object::new(moon_mass, Vector::default(), Point::new(0., 0.)).radius();
+        let distance = 20. * size;
+        let speed = (G * mass / distance).sqrt();
+        let period = 2. * PI * distance / speed;
+        let mut universe = ColorUniverse::default();
+        universe.add_object(Object::new(mass, Vector::default(), Point::new(0., 0.)),
+                            ObjectColor::FromMass);
+        universe.add_object(Object::new(moon_mass,
+                                        vector_from_components(0., speed),
+                                        Point::new(distance, 0.)),
+                            ObjectColor::FromMass);
+        let forces = Forces::new(ForceMode::Direct, 1, true);
+        universe.update_state_repeat(period / 20., 10_000, integrator, &forces);
+        let ref moon = universe.objects()[1];
+        (moon.position().x, moon.position().y, distance)
     }
-}
 
-impl Clone for CapVecDeque {
-    fn clone(&self) -> Self {
-        CapVecDeque {
-            capacity: self.capacity,
-            inner: {
-                let mut new = VecDeque::with_capacity(self.capacity);
-                new.extend(self.inner.iter().cloned());
-                new
-            }
+    // our integrators use their own G, so they should follow physics_sim's orbit
+    #[test]
+    fn integrators_agree_with_the_library() {
+        let (x, y, distance) = moon_after_step(Integrator::Library);
+        // how far the moon has been pulled off a straight line, about 5% of the orbit
+        let speed = (G * 1e16 / distance).sqrt();
+        let period = 2. * PI * distance / speed;
+        let pulled = distance - x;
+        assert!(pulled > 0.01 * distance && (y - speed * period / 20.).abs() < distance);
+        for &integrator in [Integrator::Leapfrog, Integrator::RungeKutta4].iter() {
+            let (ix, iy, _) = moon_after_step(integrator);
+            let off = ((ix - x).powi(2) + (iy - y).powi(2)).sqrt();
+            assert!(off < 1e-3 * pulled,
+                    "{} is {} off of {} pulled",
+                    integrator.name(),
+                    off,
+                    pulled);
         }
     }
 }
@@ -0,0 +1,190 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use coloruniverse::ColorUniverse;
+use super::frame::Anchor;
+use super::transition::View;
+
+pub const BOOKMARK_SLOTS: usize = 9;
+// used when there's no scenario to keep the bookmarks next to, in the user's config
+// directory
+const CONFIG_DIR: &'static str = "physics-sim-gtk";
+const DEFAULT_BOOKMARK_FILE: &'static str = "views.bookmarks";
+
+// where the bookmarks for a scenario are kept, next to it with its own extension
+pub fn bookmark_path(scenario: Option<&Path>) -> PathBuf {
+    match scenario {
+        Some(scenario) => scenario.with_extension("bookmarks"),
+        None => config_dir().join(DEFAULT_BOOKMARK_FILE),
+    }
+}
+
+// $XDG_CONFIG_HOME or ~/.config on unix, %APPDATA% on windows, falling back to the
+// working directory when none of them are set
+fn config_dir() -> PathBuf {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            match (env::var_os("HOME"), env::var_os("APPDATA")) {
+                (Some(home), _) => PathBuf::from(home).join(".config"),
+                (None, Some(app_data)) => PathBuf::from(app_data),
+                (None, None) => PathBuf::new(),
+            }
+        }
+    };
+    base.join(CONFIG_DIR)
+}
+
+// a saved camera
+#[derive(Clone)]
+pub struct ViewBookmark {
+    pub name: String,
+    pub view: View,
+    pub follow: Option<Anchor>,
+    // ids change between sessions, so a followed object is found again by its name
+    // unnamed objects are only followed in the session they were saved in
+    pub follow_name: Option<String>,
+    pub draw_grid: bool,
+    pub draw_paths: bool,
+}
+
+impl ViewBookmark {
+    // what to follow when recalled, looking the object up by name if it has one
+    pub fn resolve_follow(&self, universe: &ColorUniverse) -> Option<Anchor> {
+        match self.follow_name {
+            Some(ref name) => universe.id_named(name).map(Anchor::Object),
+            None => self.follow.clone(),
+        }
+    }
+
+    // tab separated, name first
+    fn to_line(&self) -> String {
+        let follow = match (&self.follow, &self.follow_name) {
+            (&Some(Anchor::Barycenter), _) => "barycenter".to_string(),
+            (_, &Some(ref name)) => format!("object:{}", name.replace('\t', " ")),
+            _ => "none".to_string(),
+        };
+        format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                self.name.replace('\t', " "),
+                self.view.x_scale,
+                self.view.y_scale,
+                self.view.x_shift,
+                self.view.y_shift,
+                self.view.rotation,
+                follow,
+                self.draw_grid,
+                self.draw_paths)
+    }
+
+    fn from_line(line: &str) -> Option<ViewBookmark> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 9 {
+            return None;
+        }
+        let (follow, follow_name) = match fields[6] {
+            "none" => (None, None),
+            "barycenter" => (Some(Anchor::Barycenter), None),
+            other if other.starts_with("object:") => {
+                (None, Some(other["object:".len()..].to_string()))
+            }
+            _ => return None,
+        };
+        match (fields[1].parse(),
+               fields[2].parse(),
+               fields[3].parse(),
+               fields[4].parse(),
+               fields[5].parse(),
+               fields[7].parse(),
+               fields[8].parse()) {
+            (Ok(x_scale),
+             Ok(y_scale),
+             Ok(x_shift),
+             Ok(y_shift),
+             Ok(rotation),
+             Ok(draw_grid),
+             Ok(draw_paths)) => {
+                Some(ViewBookmark {
+                    name: fields[0].to_string(),
+                    view: View {
+                        x_scale: x_scale,
+                        y_scale: y_scale,
+                        x_shift: x_shift,
+                        y_shift: y_shift,
+                        rotation: rotation,
+                    },
+                    follow: follow,
+                    follow_name: follow_name,
+                    draw_grid: draw_grid,
+                    draw_paths: draw_paths,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+pub struct ViewBookmarks {
+    slots: Vec<Option<ViewBookmark>>,
+}
+
+impl Default for ViewBookmarks {
+    fn default() -> ViewBookmarks {
+        ViewBookmarks { slots: vec![None; BOOKMARK_SLOTS] }
+    }
+}
+
+impl ViewBookmarks {
+    pub fn get(&self, slot: usize) -> Option<&ViewBookmark> {
+        self.slots.get(slot).and_then(|b| b.as_ref())
+    }
+
+    pub fn set(&mut self, slot: usize, bookmark: ViewBookmark) {
+        if slot < self.slots.len() {
+            self.slots[slot] = Some(bookmark);
+        }
+    }
+
+    pub fn rename(&mut self, slot: usize, name: String) {
+        if let Some(&mut Some(ref mut bookmark)) = self.slots.get_mut(slot) {
+            bookmark.name = name;
+        }
+    }
+
+    // slot and name of every stored bookmark
+    pub fn names(&self) -> Vec<(usize, &str)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, b)| b.as_ref().map(|b| (slot, b.name.as_str())))
+            .collect()
+    }
+
+    // one line per slot, empty lines for empty slots
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            try!(fs::create_dir_all(dir));
+        }
+        let mut file = try!(File::create(path));
+        for slot in &self.slots {
+            let line = slot.as_ref().map(|b| b.to_line()).unwrap_or(String::new());
+            try!(writeln!(file, "{}", line));
+        }
+        Ok(())
+    }
+
+    // a missing file is just an empty set of bookmarks
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ViewBookmarks> {
+        let mut bookmarks = ViewBookmarks::default();
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(bookmarks),
+            Err(e) => return Err(e),
+        };
+        for (slot, line) in BufReader::new(file).lines().enumerate().take(BOOKMARK_SLOTS) {
+            let line = try!(line);
+            bookmarks.slots[slot] = ViewBookmark::from_line(&line);
+        }
+        Ok(bookmarks)
+    }
+}
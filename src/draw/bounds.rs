@@ -0,0 +1,115 @@
+use coloruniverse::ColorUniverse;
+use trails::TrailSettings;
+use super::info::DrawInfo;
+use super::frame::Anchor;
+
+// axis aligned box in world coordinates
+#[derive(Clone, Debug)]
+pub struct Bounds {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Bounds {
+    pub fn around(x: f64, y: f64, radius: f64) -> Bounds {
+        Bounds {
+            min_x: x - radius,
+            min_y: y - radius,
+            max_x: x + radius,
+            max_y: y + radius,
+        }
+    }
+
+    pub fn include(&mut self, x: f64, y: f64, radius: f64) {
+        self.min_x = self.min_x.min(x - radius);
+        self.min_y = self.min_y.min(y - radius);
+        self.max_x = self.max_x.max(x + radius);
+        self.max_y = self.max_y.max(y + radius);
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max_y - self.min_y
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        ((self.min_x + self.max_x) / 2., (self.min_y + self.max_y) / 2.)
+    }
+
+    pub fn include_bounds(&mut self, other: &Bounds) {
+        self.include(other.min_x, other.min_y, 0.);
+        self.include(other.max_x, other.max_y, 0.);
+    }
+}
+
+// bounding box of everything that gets drawn for the objects, including their paths
+// if only is set, just that object is included
+// paths are bounded by the boxes the trails keep, so they're only included when drawn
+// in the absolute frame, relative ones would mean walking every point
+pub fn universe_bounds(universe: &ColorUniverse,
+                       info: &DrawInfo,
+                       only: Option<usize>)
+                       -> Option<Bounds> {
+    let mut bounds: Option<Bounds> = None;
+    let trails = universe.trails();
+    let include_paths = info.draw_paths() && info.path_frame().is_none();
+    for (i, (object, _, id)) in universe.object_mapped().enumerate() {
+        if only.map_or(false, |index| index != i) {
+            continue;
+        }
+        let (x, y, r) = (object.position().x, object.position().y, object.radius());
+        match bounds {
+            Some(ref mut b) => b.include(x, y, r),
+            None => bounds = Some(Bounds::around(x, y, r)),
+        }
+        let extent = trails.path(id).and_then(|path| path.extent());
+        if let (true, Some(extent), Some(b)) = (include_paths, extent, bounds.as_mut()) {
+            b.include_bounds(extent);
+        }
+    }
+    bounds
+}
+
+// everything the bounds of the whole universe depend on
+#[derive(PartialEq)]
+struct BoundsKey {
+    generation: usize,
+    steps: usize,
+    draw_paths: bool,
+    path_frame: Option<Anchor>,
+    trail_settings: TrailSettings,
+}
+
+// bounds of the whole universe, only worked out again for a new state or when what's
+// drawn changes
+#[derive(Default)]
+pub struct BoundsCache {
+    key: Option<BoundsKey>,
+    bounds: Option<Bounds>,
+}
+
+impl BoundsCache {
+    // generation is that of the ui's universe, edits don't change the number of steps
+    pub fn update(&mut self, universe: &ColorUniverse, info: &DrawInfo, generation: usize) {
+        let key = BoundsKey {
+            generation: generation,
+            steps: universe.steps(),
+            draw_paths: info.draw_paths(),
+            path_frame: info.path_frame().cloned(),
+            trail_settings: universe.trail_settings(),
+        };
+        if self.key.as_ref() != Some(&key) {
+            self.bounds = universe_bounds(universe, info, None);
+            self.key = Some(key);
+        }
+    }
+
+    pub fn bounds(&self) -> Option<&Bounds> {
+        self.bounds.as_ref()
+    }
+}
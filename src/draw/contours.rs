@@ -0,0 +1,189 @@
+use cairo::Context;
+use physics_sim::{Object, Point};
+use std::f64::consts::PI;
+use super::info::DrawInfo;
+use super::field::{FieldQuantity, FieldSamples, SampleCache, Refresh, field_at};
+
+const CONTOUR_LEVELS: usize = 16;
+const SEEDS_PER_OBJECT: usize = 12;
+// length of each field line step on screen
+const FIELD_LINE_STEP_PIXELS: f64 = 4.;
+const MAX_FIELD_LINE_STEPS: usize = 600;
+
+pub struct FieldOverlay {
+    pub contours: bool,
+    pub field_lines: bool,
+    cache: SampleCache,
+    // traced field lines in world coordinates, traced again as often as the samples
+    lines: Vec<Vec<(f64, f64)>>,
+    lines_refresh: Refresh,
+}
+
+impl Default for FieldOverlay {
+    fn default() -> FieldOverlay {
+        FieldOverlay {
+            contours: false,
+            field_lines: false,
+            cache: SampleCache::default(),
+            lines: Vec::new(),
+            lines_refresh: Refresh::default(),
+        }
+    }
+}
+
+impl FieldOverlay {
+    // off -> contours -> field lines -> both -> off
+    pub fn cycle(&mut self) {
+        let (contours, field_lines) = match (self.contours, self.field_lines) {
+            (false, false) => (true, false),
+            (true, false) => (false, true),
+            (false, true) => (true, true),
+            (true, true) => (false, false),
+        };
+        self.contours = contours;
+        self.field_lines = field_lines;
+        self.lines_refresh = Refresh::default();
+    }
+
+    // expects the identity matrix to be set, and leaves it set
+    pub fn draw(&mut self, ctxt: &Context, objects: &[Object], info: &mut DrawInfo) {
+        if self.contours {
+            let samples = self.cache.get(objects, info, FieldQuantity::Potential);
+            draw_contours(ctxt, samples);
+        }
+        if self.field_lines {
+            if self.lines_refresh.due(info) {
+                self.lines = trace_field_lines(objects, info);
+                self.lines_refresh.refreshed(info);
+            }
+            info.apply(ctxt);
+            draw_field_lines(ctxt, &self.lines, info);
+            ctxt.identity_matrix();
+        }
+    }
+}
+
+// equipotentials at log spaced levels, traced with marching squares in screen space
+fn draw_contours(ctxt: &Context, samples: &FieldSamples) {
+    let min = samples.values.iter().cloned().map(f64::abs).fold(::std::f64::INFINITY, f64::min);
+    let max = samples.values.iter().cloned().map(f64::abs).fold(0., f64::max);
+    if !(max > min) || min <= 0. {
+        return;
+    }
+    let (log_min, log_max) = (min.ln(), max.ln());
+    ctxt.new_path();
+    for level in 1..CONTOUR_LEVELS + 1 {
+        let t = level as f64 / (CONTOUR_LEVELS + 1) as f64;
+        // the potential is negative
+        let value = -(log_min + (log_max - log_min) * t).exp();
+        for row in 0..samples.rows - 1 {
+            for column in 0..samples.columns - 1 {
+                march_cell(ctxt, samples, column, row, value);
+            }
+        }
+    }
+    ctxt.set_source_rgba(0.6, 0.9, 1., 0.5);
+    ctxt.set_line_width(1.);
+    ctxt.stroke();
+}
+
+// adds the contour segments crossing a single cell to the path
+fn march_cell(ctxt: &Context, samples: &FieldSamples, column: usize, row: usize, value: f64) {
+    let size = samples.cell_size;
+    let (x, y) = (column as f64 * size, row as f64 * size);
+    // corners clockwise from the top left
+    let corners = [samples.get(column, row),
+                   samples.get(column + 1, row),
+                   samples.get(column + 1, row + 1),
+                   samples.get(column, row + 1)];
+    let mut case = 0;
+    for (i, &corner) in corners.iter().enumerate() {
+        if corner > value {
+            case |= 1 << i;
+        }
+    }
+    if case == 0 || case == 15 {
+        return;
+    }
+    // where the contour crosses each edge: top, right, bottom, left
+    let crossing = |a: f64, b: f64| (value - a) / (b - a);
+    let edge = |i: usize| -> (f64, f64) {
+        match i {
+            0 => (x + size * crossing(corners[0], corners[1]), y),
+            1 => (x + size, y + size * crossing(corners[1], corners[2])),
+            2 => (x + size * crossing(corners[3], corners[2]), y + size),
+            _ => (x, y + size * crossing(corners[0], corners[3])),
+        }
+    };
+    let center_above = corners.iter().sum::<f64>() / 4. > value;
+    let segments: &[(usize, usize)] = match case {
+        1 | 14 => &[(3, 0)],
+        2 | 13 => &[(0, 1)],
+        3 | 12 => &[(3, 1)],
+        4 | 11 => &[(1, 2)],
+        6 | 9 => &[(0, 2)],
+        7 | 8 => &[(3, 2)],
+        // saddles, decided by the value in the middle of the cell
+        5 => if center_above { &[(3, 2), (0, 1)] } else { &[(3, 0), (1, 2)] },
+        _ => if center_above { &[(3, 0), (1, 2)] } else { &[(3, 2), (0, 1)] },
+    };
+    for &(a, b) in segments {
+        let (ax, ay) = edge(a);
+        let (bx, by) = edge(b);
+        ctxt.move_to(ax, ay);
+        ctxt.line_to(bx, by);
+    }
+}
+
+// lines traced outwards along the field from evenly spaced points around each object
+fn trace_field_lines(objects: &[Object], info: &DrawInfo) -> Vec<Vec<(f64, f64)>> {
+    let step = info.get_actual_width(FIELD_LINE_STEP_PIXELS);
+    let (min_x, min_y, max_x, max_y) = info.visible_world_bounds();
+    let mut lines = Vec::with_capacity(objects.len() * SEEDS_PER_OBJECT);
+    for (i, object) in objects.iter().enumerate() {
+        for seed in 0..SEEDS_PER_OBJECT {
+            let angle = 2. * PI * seed as f64 / SEEDS_PER_OBJECT as f64;
+            let (s, c) = angle.sin_cos();
+            let start_radius = object.radius() + step;
+            let (mut x, mut y) = (object.position().x + start_radius * c,
+                                  object.position().y + start_radius * s);
+            let mut line = vec![(x, y)];
+            for _ in 0..MAX_FIELD_LINE_STEPS {
+                // the field points towards the masses, so go against it
+                let (fx, fy) = field_at(objects, x, y);
+                let magnitude = (fx * fx + fy * fy).sqrt();
+                if magnitude == 0. {
+                    break;
+                }
+                x -= step * fx / magnitude;
+                y -= step * fy / magnitude;
+                line.push((x, y));
+                let point = Point::new(x, y);
+                let inside_other = objects.iter()
+                    .enumerate()
+                    .any(|(j, o)| j != i && o.position().distance_to(&point) < o.radius());
+                if x < min_x || x > max_x || y < min_y || y > max_y || inside_other {
+                    break;
+                }
+            }
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+// expects the world transform to be applied
+fn draw_field_lines(ctxt: &Context, lines: &[Vec<(f64, f64)>], info: &DrawInfo) {
+    ctxt.new_path();
+    for line in lines {
+        if let Some(&(x, y)) = line.first() {
+            ctxt.move_to(x, y);
+        }
+        for &(x, y) in line.iter().skip(1) {
+            ctxt.line_to(x, y);
+        }
+    }
+    ctxt.set_source_rgba(1., 1., 1., 0.35);
+    ctxt.set_line_width(info.get_actual_width(1.));
+    ctxt.stroke();
+}
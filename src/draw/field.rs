@@ -0,0 +1,250 @@
+use cairo::{Context, Format, ImageSurface};
+use physics_sim::Object;
+use color::{interpolate, HEATMAP_STOPS};
+use kinematics::G;
+use time::precise_time_s;
+use super::info::DrawInfo;
+use super::transition::View;
+
+// size of each sampled cell on screen
+const CELL_PIXELS: f64 = 8.;
+// seconds between recalculations while the view stays still
+const REFRESH_TIME: f64 = 0.25;
+// and while it moves by itself, in a transition or following something
+const MOVING_REFRESH_TIME: f64 = 0.05;
+const HEATMAP_ALPHA: f64 = 0.7;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FieldQuantity {
+    Potential,
+    Magnitude,
+}
+
+// gravitational potential at a point, softened inside of objects so it stays finite
+pub fn potential_at(objects: &[Object], x: f64, y: f64) -> f64 {
+    objects.iter()
+        .map(|o| {
+            let distance = ((o.position().x - x).powi(2) + (o.position().y - y).powi(2)).sqrt();
+            -G * o.mass() / distance.max(o.radius())
+        })
+        .sum()
+}
+
+// gravitational field (acceleration) at a point, softened the same way
+pub fn field_at(objects: &[Object], x: f64, y: f64) -> (f64, f64) {
+    let (mut fx, mut fy) = (0., 0.);
+    for o in objects {
+        let (dx, dy) = (o.position().x - x, o.position().y - y);
+        let distance = (dx * dx + dy * dy).sqrt().max(o.radius());
+        if distance == 0. {
+            continue;
+        }
+        let factor = G * o.mass() / distance.powi(3);
+        fx += factor * dx;
+        fy += factor * dy;
+    }
+    (fx, fy)
+}
+
+// a field quantity sampled on a lattice of screen cells
+pub struct FieldSamples {
+    pub columns: usize,
+    pub rows: usize,
+    pub cell_size: f64,
+    // row major
+    pub values: Vec<f64>,
+}
+
+impl FieldSamples {
+    pub fn sample(objects: &[Object], info: &DrawInfo, quantity: FieldQuantity) -> FieldSamples {
+        let (x_size, y_size) = info.get_size();
+        let columns = (x_size / CELL_PIXELS).ceil() as usize + 1;
+        let rows = (y_size / CELL_PIXELS).ceil() as usize + 1;
+        let mut values = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let (x, y) = info.get_actual_point(column as f64 * CELL_PIXELS,
+                                                   row as f64 * CELL_PIXELS);
+                values.push(match quantity {
+                    FieldQuantity::Potential => potential_at(objects, x, y),
+                    FieldQuantity::Magnitude => {
+                        let (fx, fy) = field_at(objects, x, y);
+                        (fx * fx + fy * fy).sqrt()
+                    }
+                });
+            }
+        }
+        FieldSamples {
+            columns: columns,
+            rows: rows,
+            cell_size: CELL_PIXELS,
+            values: values,
+        }
+    }
+
+    pub fn get(&self, column: usize, row: usize) -> f64 {
+        self.values[row * self.columns + column]
+    }
+}
+
+// decides when something worked out for the view has to be worked out again
+pub struct Refresh {
+    refreshed_at: f64,
+    // view, size and view origin
+    refreshed_view: Option<(View, (f64, f64), (f64, f64))>,
+}
+
+impl Default for Refresh {
+    fn default() -> Refresh {
+        Refresh {
+            refreshed_at: 0.,
+            refreshed_view: None,
+        }
+    }
+}
+
+impl Refresh {
+    // right away when the view is moved by hand, often while it moves by itself,
+    // otherwise only every so often
+    pub fn due(&self, info: &DrawInfo) -> bool {
+        let (view_moved, origin_moved) = match self.refreshed_view {
+            Some((ref a, a_size, a_origin)) => {
+                let b = info.view();
+                (a.x_scale != b.x_scale || a.y_scale != b.y_scale || a.x_shift != b.x_shift ||
+                 a.y_shift != b.y_shift || a.rotation != b.rotation ||
+                 a_size != info.get_size(),
+                 a_origin != info.view_origin())
+            }
+            None => return true,
+        };
+        let elapsed = precise_time_s() - self.refreshed_at;
+        if view_moved && !info.in_transition() {
+            true
+        } else if view_moved || origin_moved {
+            elapsed > MOVING_REFRESH_TIME
+        } else {
+            elapsed > REFRESH_TIME
+        }
+    }
+
+    pub fn refreshed(&mut self, info: &DrawInfo) {
+        self.refreshed_at = precise_time_s();
+        self.refreshed_view = Some((info.view(), info.get_size(), info.view_origin()));
+    }
+}
+
+// keeps the last sampled field around so it doesn't have to be recalculated every frame
+pub struct SampleCache {
+    samples: Option<FieldSamples>,
+    quantity: Option<FieldQuantity>,
+    refresh: Refresh,
+}
+
+impl Default for SampleCache {
+    fn default() -> SampleCache {
+        SampleCache {
+            samples: None,
+            quantity: None,
+            refresh: Refresh::default(),
+        }
+    }
+}
+
+impl SampleCache {
+    // resamples if it's due, returning whether it did
+    pub fn update(&mut self,
+                  objects: &[Object],
+                  info: &DrawInfo,
+                  quantity: FieldQuantity)
+                  -> bool {
+        if self.samples.is_some() && self.quantity == Some(quantity) && !self.refresh.due(info) {
+            return false;
+        }
+        self.samples = Some(FieldSamples::sample(objects, info, quantity));
+        self.quantity = Some(quantity);
+        self.refresh.refreshed(info);
+        true
+    }
+
+    pub fn get(&mut self,
+               objects: &[Object],
+               info: &DrawInfo,
+               quantity: FieldQuantity)
+               -> &FieldSamples {
+        self.update(objects, info, quantity);
+        self.samples.as_ref().unwrap()
+    }
+}
+
+pub struct Heatmap {
+    pub quantity: Option<FieldQuantity>,
+    cache: SampleCache,
+    // the samples drawn once, painted every frame until they're resampled
+    image: Option<ImageSurface>,
+}
+
+impl Default for Heatmap {
+    fn default() -> Heatmap {
+        Heatmap {
+            quantity: None,
+            cache: SampleCache::default(),
+            image: None,
+        }
+    }
+}
+
+impl Heatmap {
+    // off -> potential -> field magnitude -> off
+    pub fn cycle(&mut self) {
+        self.quantity = match self.quantity {
+            None => Some(FieldQuantity::Potential),
+            Some(FieldQuantity::Potential) => Some(FieldQuantity::Magnitude),
+            Some(FieldQuantity::Magnitude) => None,
+        };
+    }
+
+    // expects the identity matrix to be set
+    pub fn draw(&mut self, ctxt: &Context, objects: &[Object], info: &DrawInfo) {
+        let quantity = match self.quantity {
+            Some(quantity) => quantity,
+            None => return,
+        };
+        if self.cache.update(objects, info, quantity) {
+            let (x_size, y_size) = info.get_size();
+            self.image = render_samples(self.cache.get(objects, info, quantity),
+                                        x_size.ceil() as i32,
+                                        y_size.ceil() as i32);
+        }
+        if let Some(ref image) = self.image {
+            ctxt.set_source_surface(image, 0., 0.);
+            ctxt.paint_with_alpha(HEATMAP_ALPHA);
+        }
+    }
+}
+
+// a screen sized image of the samples, None if they're all the same
+fn render_samples(samples: &FieldSamples, width: i32, height: i32) -> Option<ImageSurface> {
+    // color by the log of the magnitude, the potential is always negative
+    let logs: Vec<f64> = samples.values.iter().map(|v| (v.abs() + 1e-300).ln()).collect();
+    let min = logs.iter().cloned().fold(::std::f64::INFINITY, f64::min);
+    let max = logs.iter().cloned().fold(::std::f64::NEG_INFINITY, f64::max);
+    if !(max > min) {
+        return None;
+    }
+    let image = ImageSurface::create(Format::ARgb32, width.max(1), height.max(1));
+    let image_ctxt = Context::new(&image);
+    let half = samples.cell_size / 2.;
+    for row in 0..samples.rows {
+        for column in 0..samples.columns {
+            let t = (logs[row * samples.columns + column] - min) / (max - min);
+            let color = interpolate(&HEATMAP_STOPS, t);
+            image_ctxt.rectangle(column as f64 * samples.cell_size - half,
+                                 row as f64 * samples.cell_size - half,
+                                 samples.cell_size,
+                                 samples.cell_size);
+            image_ctxt.set_source_rgb(color.0, color.1, color.2);
+            image_ctxt.fill();
+        }
+    }
+    Some(image)
+}
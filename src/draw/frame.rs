@@ -0,0 +1,7 @@
+// something in the universe that the view can be positioned relative to
+#[derive(Clone, PartialEq, Debug)]
+pub enum Anchor {
+    Barycenter,
+    // by id, so it survives merges
+    Object(usize),
+}
@@ -0,0 +1,23 @@
+// picks a spacing of 1, 2 or 5 times a power of ten that is at least min_spacing
+// also returns how many minor lines go between each major line
+pub fn nice_spacing(min_spacing: f64) -> (f64, usize) {
+    let magnitude = 10f64.powf(min_spacing.log10().floor());
+    for &(mantissa, subdivisions) in &[(1., 5), (2., 4), (5., 5)] {
+        if mantissa * magnitude >= min_spacing {
+            return (mantissa * magnitude, subdivisions);
+        }
+    }
+    (10. * magnitude, 5)
+}
+
+// formats a multiple of spacing with just enough decimals to tell lines apart
+pub fn format_distance(value: f64, spacing: f64) -> String {
+    if value == 0. {
+        "0".to_string()
+    } else if spacing >= 1e6 || spacing < 1e-3 {
+        format!("{:e}", value)
+    } else {
+        let decimals = (-spacing.log10().floor()).max(0.) as usize;
+        format!("{:.*}", decimals, value)
+    }
+}
@@ -1,14 +1,39 @@
 use cairo::Context;
+use physics_sim::Point;
+use coloruniverse::ColorUniverse;
+use super::frame::Anchor;
+use super::bounds::Bounds;
+use super::transition::{View, Transition};
+use super::bookmarks::ViewBookmark;
+use super::grid::{nice_spacing, format_distance};
+use super::vectors::VectorSettings;
+use color::{ObjectColor, Palette};
 
 pub struct DrawInfo {
     x_size: f64,
     y_size: f64,
-    x_scale: f64,
-    y_scale: f64,
-    x_shift: f64,
-    y_shift: f64,
+    view: View,
+    // keep x and y scale equal, otherwise they can be zoomed separately
+    aspect_locked: bool,
     draw_grid: bool,
     draw_paths: bool,
+    // fade paths out towards their oldest point
+    fade_paths: bool,
+    vectors: VectorSettings,
+    palette: Palette,
+    // what computed object colors show
+    color_mode: ObjectColor,
+    // None is the absolute frame
+    frame: Option<Anchor>,
+    // paths are drawn relative to this instead of the frame when set
+    trail_frame: Option<Anchor>,
+    // object kept at the same place on the screen, None when not following anything
+    follow: Option<Anchor>,
+    // world position drawn at the view's shift, the frame origin unless following
+    view_origin: (f64, f64),
+    // refit the view to all objects every iteration
+    auto_fit: bool,
+    transition: Option<Transition>,
 }
 
 impl Default for DrawInfo {
@@ -16,29 +41,43 @@ impl Default for DrawInfo {
         DrawInfo {
             x_size: 800.,
             y_size: 800.,
-            x_scale: 0.1,
-            y_scale: 0.1,
-            x_shift: 400.,
-            y_shift: 400.,
+            view: View {
+                x_scale: 0.1,
+                y_scale: 0.1,
+                x_shift: 400.,
+                y_shift: 400.,
+                rotation: 0.,
+            },
+            aspect_locked: true,
             draw_grid: true,
             draw_paths: true,
+            fade_paths: true,
+            vectors: VectorSettings::default(),
+            palette: Palette::Classic,
+            color_mode: ObjectColor::FromMass,
+            frame: None,
+            trail_frame: None,
+            follow: None,
+            view_origin: (0., 0.),
+            auto_fit: false,
+            transition: None,
         }
     }
 }
 
 impl DrawInfo {
     pub fn get_actual_point(&self, x: f64, y: f64) -> (f64, f64) {
-        //(x * self.x_scale + self.x_shift, y * self.y_scale + self.y_shift)
-        ((x - self.x_shift) / self.x_scale, (y - self.y_shift) / self.y_scale)
+        let (x, y) = self.view.to_relative(x, y);
+        (x + self.view_origin.0, y + self.view_origin.1)
+    }
+
+    pub fn get_screen_point(&self, x: f64, y: f64) -> (f64, f64) {
+        self.view.to_screen(x - self.view_origin.0, y - self.view_origin.1)
     }
 
     // used exclusively for cairo line width
     pub fn get_actual_width(&self, width: f64) -> f64 {
-        if self.x_scale < self.y_scale {
-            width / self.x_scale
-        } else {
-            width / self.y_scale
-        }
+        width / self.view.x_scale.min(self.view.y_scale)
     }
 
     pub fn set_size(&mut self, x_size: f64, y_size: f64) {
@@ -51,33 +90,244 @@ impl DrawInfo {
     }
 
     pub fn translate(&mut self, x_trans: f64, y_trans: f64) {
-        self.x_shift += x_trans;
-        self.y_shift += y_trans;
+        self.view.x_shift += x_trans;
+        self.view.y_shift += y_trans;
+        if let Some(ref mut transition) = self.transition {
+            transition.translate(x_trans, y_trans);
+        }
+    }
+
+    pub fn view(&self) -> View {
+        self.view.clone()
+    }
+
+    // world position drawn at the view's shift
+    pub fn view_origin(&self) -> (f64, f64) {
+        self.view_origin
+    }
+
+    pub fn in_transition(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    // where the view ends up once the current transition is done
+    pub fn target_view(&self) -> View {
+        match self.transition {
+            Some(ref transition) => transition.target().clone(),
+            None => self.view(),
+        }
+    }
+
+    pub fn animate_to(&mut self, view: View, duration: f64) {
+        self.transition = Some(Transition::new(self.view(), view, duration));
+    }
+
+    // moves the view along the current transition, called every iteration
+    pub fn step_transition(&mut self) {
+        let (view, finished) = match self.transition {
+            Some(ref transition) => {
+                (transition.current(self.x_size, self.y_size), transition.finished())
+            }
+            None => return,
+        };
+        self.view = view;
+        if finished {
+            self.transition = None;
+        }
+    }
+
+    // eased, and compounding with any zoom that is still running
+    pub fn scale_smooth(&mut self,
+                        scale_center_x: f64,
+                        scale_center_y: f64,
+                        x_factor: f64,
+                        y_factor: f64) {
+        let (x_factor, y_factor) = self.lock_factors(x_factor, y_factor);
+        let view = self.target_view()
+            .scaled_about(scale_center_x, scale_center_y, x_factor, y_factor);
+        self.animate_to(view, ZOOM_TRANSITION_TIME);
+    }
+
+    // with the aspect locked, zooming a single axis zooms both
+    fn lock_factors(&self, x_factor: f64, y_factor: f64) -> (f64, f64) {
+        if !self.aspect_locked {
+            (x_factor, y_factor)
+        } else if x_factor != 1. {
+            (x_factor, x_factor)
+        } else {
+            (y_factor, y_factor)
+        }
     }
 
-    pub fn scale(&mut self,
-                 scale_center_x: f64,
-                 scale_center_y: f64,
-                 x_factor: f64,
-                 y_factor: f64) {
-        self.x_scale *= x_factor;
-        self.y_scale *= y_factor;
-        self.x_shift -= (x_factor - 1.) * (scale_center_x - self.x_shift);
-        self.y_shift -= (y_factor - 1.) * (scale_center_y - self.y_shift);
+    pub fn aspect_locked(&self) -> bool {
+        self.aspect_locked
+    }
+
+    // locking squashes the y scale back to match the x scale
+    pub fn toggle_aspect_lock(&mut self) {
+        self.aspect_locked = !self.aspect_locked;
+        if self.aspect_locked {
+            let view = self.target_view();
+            let factor = view.x_scale / view.y_scale;
+            let view = view.scaled_about(self.x_size / 2., self.y_size / 2., 1., factor);
+            self.animate_to(view, VIEW_TRANSITION_TIME);
+        }
+    }
+
+    // moves the view so the world point is in the middle of the screen
+    pub fn center_on(&mut self, x: f64, y: f64) {
+        self.transition = None;
+        let (x_size, y_size) = (self.x_size, self.y_size);
+        self.view.place(x - self.view_origin.0, y - self.view_origin.1, x_size / 2., y_size / 2.);
+    }
+
+    // rotates the view around the center of the screen
+    pub fn rotate_smooth(&mut self, angle: f64) {
+        let view = self.target_view().rotated_about(self.x_size / 2., self.y_size / 2., angle);
+        self.animate_to(view, VIEW_TRANSITION_TIME);
+    }
+
+    // zooms and shifts the view so the bounds fill it, keeping the current aspect ratio
+    // and rotation
+    pub fn fit_bounds(&mut self, bounds: &Bounds) {
+        let available_x = self.x_size * (1. - 2. * FIT_MARGIN);
+        let available_y = self.y_size * (1. - 2. * FIT_MARGIN);
+        let width = bounds.width().max(MIN_FIT_SIZE);
+        let height = bounds.height().max(MIN_FIT_SIZE);
+        let mut view = self.target_view();
+        let ratio = view.y_scale / view.x_scale;
+        // size of the rotated box on screen per unit of x scale
+        let (s, c) = view.rotation.sin_cos();
+        let (s, c) = (s.abs(), c.abs());
+        view.x_scale = (available_x / (width * c + height * ratio * s))
+            .min(available_y / (width * s + height * ratio * c));
+        view.y_scale = view.x_scale * ratio;
+        let (center_x, center_y) = bounds.center();
+        view.place(center_x - self.view_origin.0,
+                   center_y - self.view_origin.1,
+                   self.x_size / 2.,
+                   self.y_size / 2.);
+        // continuous fitting would keep restarting the transition, so jump there directly
+        if self.auto_fit {
+            self.transition = None;
+            self.view = view;
+        } else {
+            self.animate_to(view, VIEW_TRANSITION_TIME);
+        }
+    }
+
+    pub fn auto_fit(&self) -> bool {
+        self.auto_fit
+    }
+
+    pub fn toggle_auto_fit(&mut self) {
+        self.auto_fit = !self.auto_fit;
     }
 
     pub fn apply(&mut self, ctxt: &Context) {
         ctxt.identity_matrix();
-        ctxt.translate(self.x_shift, self.y_shift);
-        ctxt.scale(self.x_scale, self.y_scale);
+        ctxt.translate(self.view.x_shift, self.view.y_shift);
+        ctxt.rotate(self.view.rotation);
+        ctxt.scale(self.view.x_scale, self.view.y_scale);
+        ctxt.translate(-self.view_origin.0, -self.view_origin.1);
+    }
+
+    pub fn frame(&self) -> Option<&Anchor> {
+        self.frame.as_ref()
     }
 
+    pub fn set_frame(&mut self, universe: &ColorUniverse, frame: Option<Anchor>) {
+        let old_origin = self.view_origin;
+        self.frame = frame;
+        self.resolve_anchors(universe);
+        self.keep_view_still(old_origin);
+    }
+
+    pub fn trail_frame(&self) -> Option<&Anchor> {
+        self.trail_frame.as_ref()
+    }
+
+    pub fn set_trail_frame(&mut self, trail_frame: Option<Anchor>) {
+        self.trail_frame = trail_frame;
+    }
+
+    // what paths are drawn relative to, None for absolute paths
+    pub fn path_frame(&self) -> Option<&Anchor> {
+        self.trail_frame.as_ref().or(self.frame.as_ref())
+    }
+
+    pub fn follow(&self) -> Option<&Anchor> {
+        self.follow.as_ref()
+    }
+
+    // starts following the target from the center of the view, or stops following
+    // the zoom and any panning done while following stay user controlled
+    pub fn set_follow(&mut self, universe: &ColorUniverse, follow: Option<Anchor>) {
+        let old_origin = self.view_origin;
+        self.follow = follow;
+        self.resolve_anchors(universe);
+        self.keep_view_still(old_origin);
+        if self.follow.is_some() {
+            // glide over to the new target
+            let mut view = self.target_view();
+            view.place(0., 0., self.x_size / 2., self.y_size / 2.);
+            self.animate_to(view, VIEW_TRANSITION_TIME);
+        }
+    }
+
+    // moves the frame and view origins to wherever their anchors currently are
+    pub fn resolve_anchors(&mut self, universe: &ColorUniverse) {
+        let frame_origin = self.frame
+            .as_ref()
+            .and_then(|anchor| universe.anchor_position(anchor))
+            .unwrap_or(Point::new(0., 0.));
+        let view_origin = self.follow
+            .as_ref()
+            .and_then(|anchor| universe.anchor_position(anchor))
+            .unwrap_or(frame_origin);
+        self.view_origin = (view_origin.x, view_origin.y);
+    }
+
+    // shifts the view so that switching origins doesn't make everything jump
+    fn keep_view_still(&mut self, old_origin: (f64, f64)) {
+        let (x, y) = self.view.to_screen(self.view_origin.0 - old_origin.0,
+                                         self.view_origin.1 - old_origin.1);
+        let x_trans = x - self.view.x_shift;
+        let y_trans = y - self.view.y_shift;
+        self.translate(x_trans, y_trans);
+    }
+
+    // only the scale, shift and rotation, everything drawn stays as it is
     pub fn reset_view(&mut self) {
-        *self = DrawInfo {
-            x_size: self.x_size,
-            y_size: self.y_size,
-            ..DrawInfo::default()
+        let target = DrawInfo::default().view;
+        self.animate_to(target, VIEW_TRANSITION_TIME);
+    }
+
+    pub fn bookmark(&self, universe: &ColorUniverse, name: String) -> ViewBookmark {
+        let follow_name = match self.follow {
+            Some(Anchor::Object(id)) => {
+                universe.index_of(id).and_then(|index| universe.name(index)).map(String::from)
+            }
+            _ => None,
         };
+        ViewBookmark {
+            name: name,
+            view: self.target_view(),
+            follow: self.follow.clone(),
+            follow_name: follow_name,
+            draw_grid: self.draw_grid,
+            draw_paths: self.draw_paths,
+        }
+    }
+
+    pub fn recall_bookmark(&mut self, universe: &ColorUniverse, bookmark: &ViewBookmark) {
+        let old_origin = self.view_origin;
+        self.follow = bookmark.resolve_follow(universe);
+        self.resolve_anchors(universe);
+        self.keep_view_still(old_origin);
+        self.draw_grid = bookmark.draw_grid;
+        self.draw_paths = bookmark.draw_paths;
+        self.animate_to(bookmark.view.clone(), VIEW_TRANSITION_TIME);
     }
 
     pub fn toggle_grid(&mut self) {
@@ -92,46 +342,191 @@ impl DrawInfo {
         self.draw_paths
     }
 
+    pub fn toggle_fade_paths(&mut self) {
+        self.fade_paths = !self.fade_paths;
+    }
+
+    pub fn fade_paths(&self) -> bool {
+        self.fade_paths
+    }
+
+    pub fn palette(&self) -> Palette {
+        self.palette
+    }
+
+    pub fn next_palette(&mut self) {
+        self.palette = self.palette.next();
+    }
+
+    pub fn color_mode(&self) -> &ObjectColor {
+        &self.color_mode
+    }
+
+    pub fn next_color_mode(&mut self) {
+        self.color_mode = self.color_mode.next_mode();
+    }
+
+    pub fn vector_settings(&self) -> &VectorSettings {
+        &self.vectors
+    }
+
+    pub fn vector_settings_mut(&mut self) -> &mut VectorSettings {
+        &mut self.vectors
+    }
+
+    // axis aligned box around everything on screen, which is bigger than the screen
+    // when rotated
+    pub fn visible_world_bounds(&self) -> (f64, f64, f64, f64) {
+        let corners = [self.get_actual_point(0., 0.),
+                       self.get_actual_point(self.x_size, 0.),
+                       self.get_actual_point(0., self.y_size),
+                       self.get_actual_point(self.x_size, self.y_size)];
+        let mut bounds = (corners[0].0, corners[0].1, corners[0].0, corners[0].1);
+        for &(x, y) in &corners[1..] {
+            bounds.0 = bounds.0.min(x);
+            bounds.1 = bounds.1.min(y);
+            bounds.2 = bounds.2.max(x);
+            bounds.3 = bounds.3.max(y);
+        }
+        bounds
+    }
+
     pub fn draw_grid(&self, ctxt: &Context) {
         if self.draw_grid {
-            ctxt.set_source_rgba(1., 1., 1., 0.5);
-            ctxt.set_line_width(self.get_actual_width(0.5));
-
-            let (min_x, min_y) = self.get_actual_point(0., 0.);
-            let (max_x, max_y) = self.get_actual_point(self.x_size, self.y_size);
-            let x_range_min = (min_x / GRID_SPACING).ceil() as isize;
-            let x_range_max = (max_x / GRID_SPACING).floor() as isize;
-            let y_range_min = (min_y / GRID_SPACING).ceil() as isize;
-            let y_range_max = (max_y / GRID_SPACING).floor() as isize;
-            for x in (x_range_min..x_range_max + 1).map(|x| x as f64 * GRID_SPACING) {
-                if x == 0. {
-                    ctxt.stroke();
-                    ctxt.move_to(x, min_y);
-                    ctxt.line_to(x, max_y);
-                    ctxt.set_line_width(self.get_actual_width(1.2));
-                    ctxt.stroke();
-                    ctxt.set_line_width(self.get_actual_width(0.5));
-                } else {
-                    ctxt.move_to(x, min_y);
-                    ctxt.line_to(x, max_y);
-                }
+            let (x_spacing, x_subdivisions) = self.grid_spacing(self.view.x_scale);
+            let (y_spacing, y_subdivisions) = self.grid_spacing(self.view.y_scale);
+            self.draw_grid_lines(ctxt,
+                                 x_spacing / x_subdivisions as f64,
+                                 y_spacing / y_subdivisions as f64,
+                                 0.2);
+            self.draw_grid_lines(ctxt, x_spacing, y_spacing, 0.5);
+        }
+    }
+
+    // major grid spacing for a scale, and the number of minor lines between major lines
+    fn grid_spacing(&self, scale: f64) -> (f64, usize) {
+        nice_spacing(GRID_SPACING_PIXELS / scale)
+    }
+
+    fn draw_grid_lines(&self, ctxt: &Context, x_spacing: f64, y_spacing: f64, alpha: f64) {
+        ctxt.set_source_rgba(1., 1., 1., alpha);
+        ctxt.set_line_width(self.get_actual_width(0.5));
+
+        let (min_x, min_y, max_x, max_y) = self.visible_world_bounds();
+        let x_range_min = (min_x / x_spacing).ceil() as isize;
+        let x_range_max = (max_x / x_spacing).floor() as isize;
+        let y_range_min = (min_y / y_spacing).ceil() as isize;
+        let y_range_max = (max_y / y_spacing).floor() as isize;
+        for x in (x_range_min..x_range_max + 1).map(|x| x as f64 * x_spacing) {
+            if x == 0. {
+                ctxt.stroke();
+                ctxt.move_to(x, min_y);
+                ctxt.line_to(x, max_y);
+                ctxt.set_line_width(self.get_actual_width(1.2));
+                ctxt.stroke();
+                ctxt.set_line_width(self.get_actual_width(0.5));
+            } else {
+                ctxt.move_to(x, min_y);
+                ctxt.line_to(x, max_y);
             }
-            for y in (y_range_min..y_range_max + 1).map(|y| y as f64 * GRID_SPACING) {
-                if y == 0. {
-                    ctxt.stroke();
-                    ctxt.move_to(min_x, y);
-                    ctxt.line_to(max_x, y);
-                    ctxt.set_line_width(self.get_actual_width(1.2));
-                    ctxt.stroke();
-                    ctxt.set_line_width(self.get_actual_width(0.5));
-                } else {
-                    ctxt.move_to(min_x, y);
-                    ctxt.line_to(max_x, y);
-                }
+        }
+        for y in (y_range_min..y_range_max + 1).map(|y| y as f64 * y_spacing) {
+            if y == 0. {
+                ctxt.stroke();
+                ctxt.move_to(min_x, y);
+                ctxt.line_to(max_x, y);
+                ctxt.set_line_width(self.get_actual_width(1.2));
+                ctxt.stroke();
+                ctxt.set_line_width(self.get_actual_width(0.5));
+            } else {
+                ctxt.move_to(min_x, y);
+                ctxt.line_to(max_x, y);
             }
-            ctxt.stroke();
         }
+        ctxt.stroke();
+    }
+
+    // world coordinates of the major grid lines, drawn along the axes or the nearest
+    // screen edge when the axes are out of view
+    // expects the identity matrix to be set
+    pub fn draw_grid_labels(&self, ctxt: &Context) {
+        if !self.draw_grid {
+            return;
+        }
+        let (x_spacing, _) = self.grid_spacing(self.view.x_scale);
+        let (y_spacing, _) = self.grid_spacing(self.view.y_scale);
+        let (min_x, min_y, max_x, max_y) = self.visible_world_bounds();
+        let x_axis = 0f64.max(min_y).min(max_y);
+        let y_axis = 0f64.max(min_x).min(max_x);
+
+        ctxt.select_font_face("monospace", ::cairo::enums::FontSlant::Normal,
+                              ::cairo::enums::FontWeight::Normal);
+        ctxt.set_font_size(LABEL_SIZE);
+        ctxt.set_source_rgba(1., 1., 1., 0.7);
+        let x_range = (min_x / x_spacing).ceil() as isize..
+                      (max_x / x_spacing).floor() as isize + 1;
+        for x in x_range.map(|x| x as f64 * x_spacing) {
+            let (sx, sy) = self.get_screen_point(x, x_axis);
+            self.draw_label(ctxt, sx, sy, &format_distance(x, x_spacing));
+        }
+        let y_range = (min_y / y_spacing).ceil() as isize..
+                      (max_y / y_spacing).floor() as isize + 1;
+        for y in y_range.map(|y| y as f64 * y_spacing).filter(|&y| y != 0.) {
+            let (sx, sy) = self.get_screen_point(y_axis, y);
+            self.draw_label(ctxt, sx, sy, &format_distance(y, y_spacing));
+        }
+        ctxt.new_path();
+    }
+
+    // keeps labels just inside the screen, dropping those that would be off of it
+    fn draw_label(&self, ctxt: &Context, x: f64, y: f64, text: &str) {
+        if x < 0. || x > self.x_size || y < 0. || y > self.y_size {
+            return;
+        }
+        let x = (x + LABEL_OFFSET).min(self.x_size - LABEL_SIZE * text.len() as f64 * 0.6);
+        let y = (y - LABEL_OFFSET).max(LABEL_SIZE);
+        ctxt.move_to(x, y);
+        ctxt.show_text(text);
+    }
+
+    // bar in the bottom left corner showing a round distance and how many pixels it takes
+    // left out when the axes are scaled differently, a single length would be wrong
+    // expects the identity matrix to be set
+    pub fn draw_scale_bar(&self, ctxt: &Context) {
+        if self.view.x_scale != self.view.y_scale {
+            return;
+        }
+        let (length, _) = nice_spacing(SCALE_BAR_PIXELS / self.view.x_scale);
+        let pixels = length * self.view.x_scale;
+        let (x, y) = (SCALE_BAR_MARGIN, self.y_size - SCALE_BAR_MARGIN);
+        ctxt.new_path();
+        ctxt.move_to(x, y - 5.);
+        ctxt.line_to(x, y);
+        ctxt.line_to(x + pixels, y);
+        ctxt.line_to(x + pixels, y - 5.);
+        ctxt.set_source_rgba(1., 1., 1., 0.9);
+        ctxt.set_line_width(2.);
+        ctxt.stroke();
+        ctxt.select_font_face("monospace", ::cairo::enums::FontSlant::Normal,
+                              ::cairo::enums::FontWeight::Normal);
+        ctxt.set_font_size(LABEL_SIZE);
+        ctxt.move_to(x, y - 8.);
+        ctxt.show_text(&format!("{} per {:.0} px", format_distance(length, length), pixels));
+        ctxt.new_path();
     }
 }
 
-const GRID_SPACING: f64 = 1000.;
+// roughly how far apart major grid lines are on screen
+const GRID_SPACING_PIXELS: f64 = 100.;
+const LABEL_SIZE: f64 = 11.;
+const LABEL_OFFSET: f64 = 3.;
+// roughly how long the scale bar is on screen
+const SCALE_BAR_PIXELS: f64 = 150.;
+const SCALE_BAR_MARGIN: f64 = 20.;
+// seconds taken by view changes such as fitting or resetting
+const VIEW_TRANSITION_TIME: f64 = 0.5;
+const ZOOM_TRANSITION_TIME: f64 = 0.15;
+// fraction of the view left empty on each side when fitting
+const FIT_MARGIN: f64 = 0.05;
+// smallest world size fit to, so a single point doesn't zoom in forever
+const MIN_FIT_SIZE: f64 = 100.;
@@ -0,0 +1,36 @@
+use cairo::Context;
+use coloruniverse::ColorUniverse;
+use super::info::DrawInfo;
+
+const LABEL_SIZE: f64 = 12.;
+// gap between the edge of an object and its label, in pixels
+const LABEL_GAP: f64 = 4.;
+
+// names next to every named object, at the same size no matter the zoom
+// editing is the index and text of a name that is being typed
+// expects the identity matrix to be set
+pub fn draw_labels(ctxt: &Context,
+                   universe: &ColorUniverse,
+                   info: &DrawInfo,
+                   editing: Option<(usize, &str)>) {
+    ctxt.select_font_face("sans-serif", ::cairo::enums::FontSlant::Normal,
+                          ::cairo::enums::FontWeight::Bold);
+    ctxt.set_font_size(LABEL_SIZE);
+    for (i, object) in universe.objects().iter().enumerate() {
+        let text = match editing {
+            Some((index, text)) if index == i => format!("{}_", text),
+            _ => {
+                match universe.name(i) {
+                    Some(name) => name.to_string(),
+                    None => continue,
+                }
+            }
+        };
+        let (x, y) = info.get_screen_point(object.position().x, object.position().y);
+        let offset = object.radius() / info.get_actual_width(1.) + LABEL_GAP;
+        ctxt.move_to(x + offset, y - offset);
+        ctxt.set_source_rgba(1., 1., 1., 0.9);
+        ctxt.show_text(&text);
+    }
+    ctxt.new_path();
+}
@@ -0,0 +1,52 @@
+use cairo::Context;
+use color::{ColorContext, ObjectColor};
+use super::info::DrawInfo;
+
+const LEGEND_WIDTH: f64 = 160.;
+const LEGEND_HEIGHT: f64 = 10.;
+const LEGEND_MARGIN: f64 = 15.;
+const LEGEND_STEPS: usize = 64;
+const LEGEND_TEXT_SIZE: f64 = 11.;
+
+// gradient with the range of values it covers, in the top right corner
+// expects the identity matrix to be set
+pub fn draw_legend(ctxt: &Context, info: &DrawInfo, context: &ColorContext) {
+    let ref mode = context.mode;
+    if let ObjectColor::UserSet(_) = *mode {
+        return;
+    }
+    let range = context.range(mode);
+    if range.min > range.max {
+        // nothing to color
+        return;
+    }
+    let (x_size, _) = info.get_size();
+    let (x, y) = (x_size - LEGEND_WIDTH - LEGEND_MARGIN, LEGEND_MARGIN + LEGEND_TEXT_SIZE + 4.);
+    let step_width = LEGEND_WIDTH / LEGEND_STEPS as f64;
+    for i in 0..LEGEND_STEPS {
+        let color = context.palette.color(i as f64 / (LEGEND_STEPS - 1) as f64);
+        ctxt.rectangle(x + i as f64 * step_width, y, step_width + 0.5, LEGEND_HEIGHT);
+        color_func!(ctxt, set_source_rgb, color);
+        ctxt.fill();
+    }
+
+    let format_value = |value: f64| if range.log || value.abs() >= 1e5 {
+        format!("{:.2e}", value)
+    } else {
+        format!("{:.1}", value)
+    };
+    ctxt.select_font_face("monospace", ::cairo::enums::FontSlant::Normal,
+                          ::cairo::enums::FontWeight::Normal);
+    ctxt.set_font_size(LEGEND_TEXT_SIZE);
+    ctxt.set_source_rgb(1., 1., 1.);
+    let scale = if range.log { "log " } else { "" };
+    ctxt.move_to(x, y - 4.);
+    ctxt.show_text(&format!("{}{} ({})", scale, mode.name(), context.palette.name()));
+    ctxt.move_to(x, y + LEGEND_HEIGHT + LEGEND_TEXT_SIZE + 2.);
+    ctxt.show_text(&format_value(range.min));
+    let max = format_value(range.max);
+    ctxt.move_to(x + LEGEND_WIDTH - max.len() as f64 * LEGEND_TEXT_SIZE * 0.6,
+                 y + LEGEND_HEIGHT + LEGEND_TEXT_SIZE + 2.);
+    ctxt.show_text(&max);
+    ctxt.new_path();
+}
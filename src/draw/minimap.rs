@@ -0,0 +1,102 @@
+use cairo::Context;
+use coloruniverse::ColorUniverse;
+use color::ColorContext;
+use std::f64::consts::PI;
+use super::info::DrawInfo;
+use super::bounds::Bounds;
+
+const MINIMAP_SIZE: f64 = 180.;
+const MINIMAP_MARGIN: f64 = 10.;
+// smallest radius objects are drawn with on the minimap
+const MIN_DOT_RADIUS: f64 = 1.5;
+// smallest world size shown, so a single object doesn't fill the whole map
+const MIN_MAP_SIZE: f64 = 1000.;
+
+// maps world coordinates into the minimap's corner of the screen
+pub struct Minimap {
+    x: f64,
+    y: f64,
+    scale: f64,
+    center_x: f64,
+    center_y: f64,
+}
+
+impl Minimap {
+    // fits the bounds of every object into the bottom right corner, or the current view
+    // if there aren't any
+    // the view isn't included otherwise, so the map stays still while dragging in it
+    pub fn new(bounds: Option<&Bounds>, info: &DrawInfo) -> Minimap {
+        let (x_size, y_size) = info.get_size();
+        let bounds = bounds.cloned().unwrap_or_else(|| {
+            let (min_x, min_y, max_x, max_y) = info.visible_world_bounds();
+            let mut bounds = Bounds::around(min_x, min_y, 0.);
+            bounds.include(max_x, max_y, 0.);
+            bounds
+        });
+        let (center_x, center_y) = bounds.center();
+        let inner = MINIMAP_SIZE - 2. * MINIMAP_MARGIN;
+        Minimap {
+            x: x_size - MINIMAP_SIZE - MINIMAP_MARGIN,
+            y: y_size - MINIMAP_SIZE - MINIMAP_MARGIN,
+            scale: (inner / bounds.width().max(MIN_MAP_SIZE))
+                .min(inner / bounds.height().max(MIN_MAP_SIZE)),
+            center_x: center_x,
+            center_y: center_y,
+        }
+    }
+
+    pub fn contains(&self, screen_x: f64, screen_y: f64) -> bool {
+        screen_x >= self.x && screen_x <= self.x + MINIMAP_SIZE && screen_y >= self.y &&
+        screen_y <= self.y + MINIMAP_SIZE
+    }
+
+    pub fn to_minimap(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.x + MINIMAP_SIZE / 2. + (x - self.center_x) * self.scale,
+         self.y + MINIMAP_SIZE / 2. + (y - self.center_y) * self.scale)
+    }
+
+    pub fn to_world(&self, screen_x: f64, screen_y: f64) -> (f64, f64) {
+        (self.center_x + (screen_x - self.x - MINIMAP_SIZE / 2.) / self.scale,
+         self.center_y + (screen_y - self.y - MINIMAP_SIZE / 2.) / self.scale)
+    }
+
+    // expects the identity matrix to be set
+    pub fn draw(&self, ctxt: &Context, universe: &ColorUniverse, info: &DrawInfo) {
+        ctxt.new_path();
+        ctxt.rectangle(self.x, self.y, MINIMAP_SIZE, MINIMAP_SIZE);
+        ctxt.set_source_rgba(0.1, 0.1, 0.1, 0.85);
+        ctxt.fill_preserve();
+        ctxt.set_source_rgba(1., 1., 1., 0.6);
+        ctxt.set_line_width(1.);
+        ctxt.stroke();
+
+        ctxt.save();
+        ctxt.rectangle(self.x, self.y, MINIMAP_SIZE, MINIMAP_SIZE);
+        ctxt.clip();
+        let context = ColorContext::new(universe, info.palette(), info.color_mode().clone());
+        for (object, color, _) in universe.object_mapped() {
+            let (x, y) = self.to_minimap(object.position().x, object.position().y);
+            let color = color.resolve(object, &context);
+            ctxt.arc(x,
+                     y,
+                     (object.radius() * self.scale).max(MIN_DOT_RADIUS),
+                     0.,
+                     2. * PI);
+            color_func!(ctxt, set_source_rgb, color);
+            ctxt.fill();
+        }
+
+        // outline of the main view, which is only a rectangle when not rotated
+        let (x_size, y_size) = info.get_size();
+        for &(sx, sy) in &[(0., 0.), (x_size, 0.), (x_size, y_size), (0., y_size)] {
+            let (wx, wy) = info.get_actual_point(sx, sy);
+            let (x, y) = self.to_minimap(wx, wy);
+            ctxt.line_to(x, y);
+        }
+        ctxt.close_path();
+        ctxt.set_source_rgba(1., 1., 0.4, 0.9);
+        ctxt.set_line_width(1.);
+        ctxt.stroke();
+        ctxt.restore();
+    }
+}
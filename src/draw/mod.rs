@@ -1,6 +1,19 @@
 use cairo::Context;
 mod info;
 mod traits;
+mod orbit;
+mod frame;
+mod bounds;
+mod transition;
+mod bookmarks;
+mod grid;
+mod minimap;
+mod labels;
+mod vectors;
+mod field;
+mod contours;
+mod legend;
+mod tree;
 
 pub fn draw_arrow_head(ctxt: &Context,
                        head_x: f64,
@@ -26,6 +39,36 @@ pub fn draw_arrow_head(ctxt: &Context,
     ctxt.fill();
 }
 
+pub const TEXT_SIZE: f64 = 13.;
+pub const TEXT_LINE_HEIGHT: f64 = 16.;
+
+// draws lines of text in screen space, starting with the top left corner at (x, y)
+// expects the identity matrix to be set
+pub fn draw_text_lines(ctxt: &Context, x: f64, y: f64, lines: &[String], r: f64, g: f64, b: f64) {
+    ctxt.select_font_face("monospace", ::cairo::enums::FontSlant::Normal,
+                          ::cairo::enums::FontWeight::Normal);
+    ctxt.set_font_size(TEXT_SIZE);
+    ctxt.set_source_rgb(r, g, b);
+    for (i, line) in lines.iter().enumerate() {
+        ctxt.move_to(x, y + TEXT_SIZE + i as f64 * TEXT_LINE_HEIGHT);
+        ctxt.show_text(line);
+    }
+    ctxt.new_path();
+}
+
 pub use self::info::DrawInfo as DrawInfo;
+pub use self::frame::Anchor as Anchor;
+pub use self::bounds::Bounds as Bounds;
+pub use self::transition::View as View;
+pub use self::bookmarks::{ViewBookmark, ViewBookmarks, bookmark_path};
+pub use self::minimap::Minimap as Minimap;
+pub use self::labels::draw_labels as draw_labels;
+pub use self::vectors::draw_vectors as draw_vectors;
+pub use self::field::Heatmap as Heatmap;
+pub use self::contours::FieldOverlay as FieldOverlay;
+pub use self::legend::draw_legend as draw_legend;
+pub use self::bounds::{universe_bounds, BoundsCache};
+pub use self::orbit::draw_orbit as draw_orbit;
+pub use self::tree::draw_tree as draw_tree;
 pub use self::traits::DrawAll as DrawAll;
 pub use self::traits::Draw as Draw;
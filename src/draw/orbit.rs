@@ -0,0 +1,51 @@
+use cairo::Context;
+use physics_sim::Object;
+use orbit::OrbitalElements;
+use std::f64::consts::PI;
+use super::info::DrawInfo;
+
+const HYPERBOLA_SEGMENTS: usize = 200;
+// how close to the asymptotes a hyperbolic trajectory is drawn
+const ASYMPTOTE_MARGIN: f64 = 0.02;
+
+// draws the conic described by the elements around the primary, in world coordinates
+pub fn draw_orbit(ctxt: &Context, info: &DrawInfo, primary: &Object, elements: &OrbitalElements) {
+    let (px, py) = (primary.position().x, primary.position().y);
+    let omega = elements.argument_of_periapsis;
+    ctxt.new_path();
+    if elements.is_bound() {
+        let a = elements.semi_major_axis;
+        let b = a * (1. - elements.eccentricity.powi(2)).sqrt();
+        // the primary sits at a focus, so the center is offset away from the periapsis
+        let (s, c) = omega.sin_cos();
+        let (cx, cy) = (px - a * elements.eccentricity * c, py - a * elements.eccentricity * s);
+        ctxt.save();
+        ctxt.translate(cx, cy);
+        ctxt.rotate(omega);
+        ctxt.scale(a, b);
+        ctxt.arc(0., 0., 1., 0., 2. * PI);
+        ctxt.restore();
+    } else {
+        // only the branch around the primary, stopping short of the asymptotes
+        let max_anomaly = (-1. / elements.eccentricity).acos() - ASYMPTOTE_MARGIN;
+        for i in 0..HYPERBOLA_SEGMENTS + 1 {
+            let anomaly = -max_anomaly + 2. * max_anomaly * i as f64 / HYPERBOLA_SEGMENTS as f64;
+            let r = elements.radius_at(anomaly);
+            let angle = omega + anomaly;
+            let (s, c) = angle.sin_cos();
+            ctxt.line_to(px + r * c, py + r * s);
+        }
+    }
+    ctxt.set_source_rgba(1., 1., 0.4, 0.6);
+    ctxt.set_line_width(info.get_actual_width(1.));
+    ctxt.stroke();
+
+    // mark the periapsis
+    let (s, c) = omega.sin_cos();
+    ctxt.arc(px + elements.periapsis * c,
+             py + elements.periapsis * s,
+             info.get_actual_width(3.),
+             0.,
+             2. * PI);
+    ctxt.fill();
+}
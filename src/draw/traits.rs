@@ -1,42 +1,70 @@
 use physics_sim::{Object, Point};
 use cairo::Context;
-use color::{ObjectColor, mass_to_color};
-use coloruniverse::{ColorUniverse, CapVecDeque};
+use color::{Color, ColorContext};
+use coloruniverse::ColorUniverse;
 use super::info::DrawInfo;
 
+// number of separately stroked pieces a faded path is split into
+const FADE_STEPS: usize = 16;
+// opacity and width of the oldest part of a faded path, relative to the newest
+const FADE_MIN_ALPHA: f64 = 0.05;
+const FADE_MIN_WIDTH: f64 = 0.3;
+const PATH_WIDTH: f64 = 1.;
+// samples closer together than this on screen are merged
+const DECIMATION_PIXELS: f64 = 0.75;
+
 pub trait DrawAll {
     fn draw_all(&self, &Context, &DrawInfo);
 }
 
 impl DrawAll for ColorUniverse {
     fn draw_all(&self, ctxt: &Context, info: &DrawInfo) {
+        let context = ColorContext::new(self, info.palette(), info.color_mode().clone());
         if info.draw_paths() {
-            for (object, color, positions) in self.object_mapped() {
-                object.draw_path(ctxt, info, color, positions);
+            let origin = info.path_frame().and_then(|anchor| self.anchor_position(anchor));
+            // each path is copied out under its own short read lock, so the updater
+            // isn't kept waiting to sample trails while they're drawn
+            let mut points: Vec<Point> = Vec::new();
+            for (object, color, id) in self.object_mapped() {
+                points.clear();
+                {
+                    let trails = self.trails();
+                    let positions = match trails.path(id) {
+                        Some(positions) => positions,
+                        None => continue,
+                    };
+                    let history = info.path_frame()
+                        .and_then(|anchor| self.anchor_history(&trails, anchor));
+                    // the updater may have sampled ahead of this state
+                    match (history, origin.as_ref()) {
+                        (Some(history), Some(origin)) => {
+                            points.extend(positions.relative_to(history, origin, self.clock()))
+                        }
+                        _ => {
+                            let len = positions.len_until(self.clock());
+                            points.extend(positions.iter().cloned().take(len))
+                        }
+                    }
+                }
+                let color = color.resolve(object, &context);
+                object.draw_path(ctxt, info, &color, points.iter().cloned(), points.len());
             }
         }
 
         for (object, color, _) in self.object_mapped() {
-            object.draw(ctxt, info, color);
+            object.draw(ctxt, info, &color.resolve(object, &context));
         }
     }
 }
 
 pub trait Draw {
-    fn draw(&self, &Context, &DrawInfo, &ObjectColor);
-    fn draw_path(&self, &Context, &DrawInfo, &ObjectColor, &CapVecDeque);
+    fn draw(&self, &Context, &DrawInfo, &Color);
+    // only the first len positions are drawn
+    fn draw_path<I: Iterator<Item = Point>>(&self, &Context, &DrawInfo, &Color, I, usize);
 }
 
 impl Draw for Object {
-    fn draw(&self, ctxt: &Context, info: &DrawInfo, color: &ObjectColor) {
-        let ctmp;
-        let color = match *color {
-            ObjectColor::UserSet(ref c) => c,
-            ObjectColor::FromMass => {
-                ctmp = mass_to_color(self.mass());
-                &ctmp
-            }
-        };
+    fn draw(&self, ctxt: &Context, info: &DrawInfo, color: &Color) {
         color_func!(ctxt, set_source_rgb, color);
 
         // draw the object
@@ -56,68 +84,65 @@ impl Draw for Object {
         ctxt.stroke();
     }
 
-    fn draw_path(&self,
-                 ctxt: &Context,
-                 info: &DrawInfo,
-                 color: &ObjectColor,
-                 positions: &CapVecDeque) {
-        let ctmp;
-        let color = match *color {
-            ObjectColor::UserSet(ref c) => c,
-            ObjectColor::FromMass => {
-                ctmp = mass_to_color(self.mass());
-                &ctmp
-            }
+    fn draw_path<I>(&self,
+                    ctxt: &Context,
+                    info: &DrawInfo,
+                    color: &Color,
+                    positions: I,
+                    len: usize)
+        where I: Iterator<Item = Point>
+    {
+        if len == 0 {
+            return;
+        }
+        let min_distance_squared = info.get_actual_width(DECIMATION_PIXELS).powi(2);
+        // a faded path is stroked in pieces from tail to head, each one more opaque and
+        // wider than the last
+        let pieces = if info.fade_paths() && len >= FADE_STEPS {
+            FADE_STEPS
+        } else {
+            1
         };
-        color_func!(ctxt, set_source_rgb, color);
-        // draw the path
-        let current = self.position();
-        ctxt.set_line_width(info.get_actual_width(1.));
-        draw_positions(&self.position(), ctxt, positions);
+        let mut piece = 0;
+        set_piece_style(ctxt, info, color, piece, pieces);
+        let mut last: Option<Point> = None;
+        for (i, pt) in positions.take(len).enumerate() {
+            let piece_end = i == len - 1 ||
+                            (piece + 1 < pieces && i == len * (piece + 1) / pieces);
+            match last {
+                None => ctxt.move_to(pt.x, pt.y),
+                // samples too close to the last one emitted are skipped, but pieces
+                // always end where they should
+                Some(l) => {
+                    let (dx, dy) = (pt.x - l.x, pt.y - l.y);
+                    if dx * dx + dy * dy < min_distance_squared && !piece_end {
+                        continue;
+                    }
+                    ctxt.line_to(pt.x, pt.y);
+                }
+            }
+            last = Some(pt);
+            // the next piece starts at the same point so they join up
+            if piece_end && i != len - 1 {
+                ctxt.stroke();
+                piece += 1;
+                set_piece_style(ctxt, info, color, piece, pieces);
+                ctxt.move_to(pt.x, pt.y);
+            }
+        }
         ctxt.stroke();
     }
 }
 
-fn draw_positions(current: &Point, ctxt: &Context, positions: &CapVecDeque) {
-    match positions.len() {
-        0 => {}
-        1 => {
-            ctxt.line_to(positions[0].x, positions[0].y);
-        }
-        2 => {
-            ctxt.curve_to(positions[0].x,
-                          positions[0].y,
-                          positions[0].x,
-                          positions[0].y,
-                          positions[1].x,
-                          positions[1].y);
-        }
-        3 => {
-            ctxt.curve_to(positions[0].x,
-                          positions[0].y,
-                          positions[1].x,
-                          positions[1].y,
-                          positions[2].x,
-                          positions[2].y);
-        }
-        _ => {
-            let max_mult = (positions.len() - 1) / 3;
-            for i in (0..max_mult).map(|x| x * 3) {
-                ctxt.curve_to(positions[i].x,
-                              positions[i].y,
-                              positions[i + 1].x,
-                              positions[i + 1].y,
-                              positions[i + 2].x,
-                              positions[i + 2].y);
-            }
-            let remaining = {
-                let mut new = CapVecDeque::with_capacity(positions.len() - max_mult * 3);
-                for i in max_mult * 3..positions.len() {
-                    new.push_back(positions[i])
-                }
-                new
-            };
-            draw_positions(&positions[max_mult * 3 - 1], ctxt, &remaining);
-        }
+fn set_piece_style(ctxt: &Context, info: &DrawInfo, color: &Color, piece: usize, pieces: usize) {
+    if pieces == 1 {
+        color_func!(ctxt, set_source_rgb, color);
+        ctxt.set_line_width(info.get_actual_width(PATH_WIDTH));
+        return;
     }
+    let t = (piece + 1) as f64 / pieces as f64;
+    let alpha = FADE_MIN_ALPHA + (1. - FADE_MIN_ALPHA) * t;
+    let width = PATH_WIDTH * (FADE_MIN_WIDTH + (1. - FADE_MIN_WIDTH) * t);
+    ctxt.set_source_rgba(color.0, color.1, color.2, alpha);
+    ctxt.set_line_width(info.get_actual_width(width));
 }
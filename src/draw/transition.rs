@@ -0,0 +1,113 @@
+use time::precise_time_s;
+
+// the part of the view that gets animated
+// screen = shift + rotate(rotation) * scale * (world - view origin)
+#[derive(Clone, Debug)]
+pub struct View {
+    pub x_scale: f64,
+    pub y_scale: f64,
+    pub x_shift: f64,
+    pub y_shift: f64,
+    pub rotation: f64,
+}
+
+impl View {
+    // point relative to the view origin that is drawn at the given screen position
+    pub fn to_relative(&self, screen_x: f64, screen_y: f64) -> (f64, f64) {
+        let (x, y) = (screen_x - self.x_shift, screen_y - self.y_shift);
+        let (s, c) = self.rotation.sin_cos();
+        ((c * x + s * y) / self.x_scale, (-s * x + c * y) / self.y_scale)
+    }
+
+    // screen position of a point relative to the view origin
+    pub fn to_screen(&self, x: f64, y: f64) -> (f64, f64) {
+        let (x, y) = (x * self.x_scale, y * self.y_scale);
+        let (s, c) = self.rotation.sin_cos();
+        (c * x - s * y + self.x_shift, s * x + c * y + self.y_shift)
+    }
+
+    // moves the view so the relative point is drawn at the screen position
+    pub fn place(&mut self, x: f64, y: f64, screen_x: f64, screen_y: f64) {
+        self.x_shift = 0.;
+        self.y_shift = 0.;
+        let (unshifted_x, unshifted_y) = self.to_screen(x, y);
+        self.x_shift = screen_x - unshifted_x;
+        self.y_shift = screen_y - unshifted_y;
+    }
+
+    // scales along the world axes, keeping whatever is under the center in place
+    pub fn scaled_about(&self, center_x: f64, center_y: f64, x_factor: f64, y_factor: f64) -> View {
+        let (x, y) = self.to_relative(center_x, center_y);
+        let mut view = self.clone();
+        view.x_scale *= x_factor;
+        view.y_scale *= y_factor;
+        view.place(x, y, center_x, center_y);
+        view
+    }
+
+    pub fn rotated_about(&self, center_x: f64, center_y: f64, angle: f64) -> View {
+        let (x, y) = self.to_relative(center_x, center_y);
+        let mut view = self.clone();
+        view.rotation += angle;
+        view.place(x, y, center_x, center_y);
+        view
+    }
+}
+
+pub struct Transition {
+    from: View,
+    to: View,
+    start_time: f64,
+    duration: f64,
+}
+
+impl Transition {
+    pub fn new(from: View, to: View, duration: f64) -> Transition {
+        Transition {
+            from: from,
+            to: to,
+            start_time: precise_time_s(),
+            duration: duration,
+        }
+    }
+
+    pub fn target(&self) -> &View {
+        &self.to
+    }
+
+    pub fn finished(&self) -> bool {
+        precise_time_s() >= self.start_time + self.duration
+    }
+
+    // keeps manual panning working while a transition is running
+    pub fn translate(&mut self, x_trans: f64, y_trans: f64) {
+        self.from.x_shift += x_trans;
+        self.from.y_shift += y_trans;
+        self.to.x_shift += x_trans;
+        self.to.y_shift += y_trans;
+    }
+
+    // scale is interpolated geometrically and the point at the center of the screen
+    // linearly, so zooming looks uniform and doesn't swing sideways
+    pub fn current(&self, x_size: f64, y_size: f64) -> View {
+        let t = ((precise_time_s() - self.start_time) / self.duration).max(0.).min(1.);
+        let t = ease(t);
+        let (cx, cy) = (x_size / 2., y_size / 2.);
+        let (from_x, from_y) = self.from.to_relative(cx, cy);
+        let (to_x, to_y) = self.to.to_relative(cx, cy);
+        let mut view = View {
+            x_scale: self.from.x_scale * (self.to.x_scale / self.from.x_scale).powf(t),
+            y_scale: self.from.y_scale * (self.to.y_scale / self.from.y_scale).powf(t),
+            x_shift: 0.,
+            y_shift: 0.,
+            rotation: self.from.rotation + (self.to.rotation - self.from.rotation) * t,
+        };
+        view.place(from_x + (to_x - from_x) * t, from_y + (to_y - from_y) * t, cx, cy);
+        view
+    }
+}
+
+// smoothstep, slow at both ends
+fn ease(t: f64) -> f64 {
+    t * t * (3. - 2. * t)
+}
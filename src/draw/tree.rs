@@ -0,0 +1,22 @@
+use cairo::Context;
+use physics_sim::Object;
+use barneshut::QuadTree;
+use super::info::DrawInfo;
+
+// outlines the cells of the barnes-hut tree for the objects, in world coordinates
+pub fn draw_tree(ctxt: &Context, info: &DrawInfo, objects: &[Object]) {
+    let masses: Vec<f64> = objects.iter().map(|o| o.mass()).collect();
+    let positions: Vec<(f64, f64)> =
+        objects.iter().map(|o| (o.position().x, o.position().y)).collect();
+    let tree = match QuadTree::build(&masses, &positions) {
+        Some(tree) => tree,
+        None => return,
+    };
+    ctxt.new_path();
+    for (x, y, half_size) in tree.cells() {
+        ctxt.rectangle(x - half_size, y - half_size, 2. * half_size, 2. * half_size);
+    }
+    ctxt.set_source_rgba(0.3, 0.8, 0.3, 0.4);
+    ctxt.set_line_width(info.get_actual_width(1.));
+    ctxt.stroke();
+}
@@ -0,0 +1,119 @@
+use cairo::Context;
+use coloruniverse::ColorUniverse;
+use kinematics::{velocity, accelerations};
+use super::info::DrawInfo;
+use super::draw_arrow_head;
+use super::frame::Anchor;
+
+// log scaled arrows grow about linearly up to this many pixels, then with the log
+const LOG_ARROW_PIXELS: f64 = 80.;
+pub const MIN_VECTOR_SCALE: f64 = 0.05;
+pub const MAX_VECTOR_SCALE: f64 = 50.;
+
+pub struct VectorSettings {
+    pub velocity: bool,
+    pub acceleration: bool,
+    // user adjustable multiplier for the arrow lengths
+    pub scale: f64,
+    // lengths grow with the log of the magnitude, so small vectors stay visible
+    pub log_scale: bool,
+}
+
+impl Default for VectorSettings {
+    fn default() -> VectorSettings {
+        VectorSettings {
+            velocity: false,
+            acceleration: false,
+            scale: 1.,
+            log_scale: false,
+        }
+    }
+}
+
+impl VectorSettings {
+    pub fn multiply_scale(&mut self, factor: f64) {
+        self.scale = (self.scale * factor).max(MIN_VECTOR_SCALE).min(MAX_VECTOR_SCALE);
+    }
+
+    // arrow length in pixels for a vector that moves its object reach times its
+    // magnitude, so an arrow only changes when its vector does
+    fn length(&self, magnitude: f64, reach: f64, info: &DrawInfo) -> f64 {
+        let pixels = magnitude * reach * self.scale / info.get_actual_width(1.);
+        if self.log_scale {
+            LOG_ARROW_PIXELS * (1. + pixels / LOG_ARROW_PIXELS).ln()
+        } else {
+            pixels
+        }
+    }
+}
+
+// time is how far ahead the arrows look in simulated time: velocity arrows end where
+// the object would get to without being pulled, acceleration arrows are how far the
+// pull moves it off of that
+// both are relative to the reference frame, like the paths
+// expects the world transform to be applied
+pub fn draw_vectors(ctxt: &Context, universe: &ColorUniverse, info: &DrawInfo, time: f64) {
+    let settings = info.vector_settings();
+    if settings.velocity {
+        let (fvx, fvy) = info.frame()
+            .and_then(|anchor| universe.anchor_velocity(anchor))
+            .unwrap_or((0., 0.));
+        let velocities: Vec<(f64, f64)> = universe.objects()
+            .iter()
+            .map(|object| {
+                let (vx, vy) = velocity(object);
+                (vx - fvx, vy - fvy)
+            })
+            .collect();
+        draw_arrows(ctxt, universe, info, &velocities, time, (0.4, 0.8, 1.));
+    }
+    if settings.acceleration {
+        let mut accelerations = accelerations(universe.objects());
+        // the barycenter never accelerates
+        let frame_index = match info.frame() {
+            Some(&Anchor::Object(id)) => universe.index_of(id),
+            _ => None,
+        };
+        if let Some((fax, fay)) = frame_index.map(|index| accelerations[index]) {
+            for a in accelerations.iter_mut() {
+                *a = (a.0 - fax, a.1 - fay);
+            }
+        }
+        draw_arrows(ctxt, universe, info, &accelerations, 0.5 * time * time, (1., 0.5, 0.3));
+    }
+}
+
+fn draw_arrows(ctxt: &Context,
+               universe: &ColorUniverse,
+               info: &DrawInfo,
+               vectors: &[(f64, f64)],
+               reach: f64,
+               color: (f64, f64, f64)) {
+    for (object, &(x, y)) in universe.objects().iter().zip(vectors) {
+        let length = info.vector_settings().length((x * x + y * y).sqrt(), reach, info);
+        if length == 0. {
+            continue;
+        }
+        let angle = y.atan2(x);
+        let length = info.get_actual_width(length);
+        let (s, c) = angle.sin_cos();
+        let (start_x, start_y) = (object.position().x, object.position().y);
+        let (end_x, end_y) = (start_x + length * c, start_y + length * s);
+        ctxt.new_path();
+        ctxt.move_to(start_x, start_y);
+        ctxt.line_to(end_x, end_y);
+        ctxt.set_source_rgba(color.0, color.1, color.2, 0.8);
+        ctxt.set_line_width(info.get_actual_width(1.5));
+        ctxt.stroke();
+        draw_arrow_head(ctxt,
+                        end_x,
+                        end_y,
+                        angle,
+                        25f64.to_radians(),
+                        info.get_actual_width(8.),
+                        color.0,
+                        color.1,
+                        color.2,
+                        0.8);
+    }
+}
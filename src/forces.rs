@@ -0,0 +1,233 @@
+use std::sync::{Arc, RwLock};
+use barneshut::{ForceMode, QuadTree};
+use kinematics::{G, Pull, pull_on};
+use threadpool::ThreadPool;
+
+// chunks of work per thread, more than one so threads that finish early can take more
+const CHUNKS_PER_THREAD: usize = 4;
+// below this many objects threads cost more than they save
+pub const MIN_PARALLEL_OBJECTS: usize = 64;
+
+// works out pulls with the chosen force mode, spread over threads
+pub struct Forces {
+    pub mode: ForceMode,
+    // give the exact same results as a single thread, otherwise pairs are only
+    // worked out once and the sums can come out in any order
+    pub deterministic: bool,
+    // None runs on the updater thread
+    pool: Option<ThreadPool>,
+    // what the threads work from, kept between evaluations so it isn't allocated
+    // again every time
+    bodies: Arc<RwLock<Bodies>>,
+}
+
+#[derive(Default)]
+struct Bodies {
+    masses: Vec<f64>,
+    positions: Vec<(f64, f64)>,
+}
+
+impl Forces {
+    pub fn new(mode: ForceMode, threads: usize, deterministic: bool) -> Forces {
+        let mut forces = Forces {
+            mode: mode,
+            deterministic: deterministic,
+            pool: None,
+            bodies: Arc::new(RwLock::new(Bodies::default())),
+        };
+        forces.set_threads(threads);
+        forces
+    }
+
+    pub fn threads(&self) -> usize {
+        self.pool.as_ref().map_or(1, |pool| pool.threads())
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        if threads != self.threads() {
+            self.pool = if threads > 1 {
+                Some(ThreadPool::new(threads))
+            } else {
+                None
+            };
+        }
+    }
+
+    pub fn pulls(&self, masses: &[f64], positions: &[(f64, f64)]) -> Vec<Pull> {
+        let pool = match self.pool {
+            Some(ref pool) if positions.len() >= MIN_PARALLEL_OBJECTS => pool,
+            _ => return self.mode.pulls(masses, positions),
+        };
+        let chunks = pool.threads() * CHUNKS_PER_THREAD;
+        {
+            let mut bodies = self.bodies.write().unwrap();
+            bodies.masses.clear();
+            bodies.masses.extend_from_slice(masses);
+            bodies.positions.clear();
+            bodies.positions.extend_from_slice(positions);
+        }
+        let bodies = self.bodies.clone();
+        let len = positions.len();
+        match self.mode {
+            // every body is summed up the same way as on one thread
+            ForceMode::Direct if self.deterministic => {
+                pool.run_ordered(len,
+                                 chunks,
+                                 Arc::new(move |start: usize, end: usize| {
+                                     let bodies = bodies.read().unwrap();
+                                     (start..end)
+                                         .map(|i| pull_on(i, &bodies.masses, &bodies.positions))
+                                         .collect()
+                                 }))
+            }
+            ForceMode::Direct => {
+                let partials = pool.run(len,
+                                        chunks,
+                                        Arc::new(move |start: usize, end: usize| {
+                                            let bodies = bodies.read().unwrap();
+                                            pair_pulls(start,
+                                                       end,
+                                                       &bodies.masses,
+                                                       &bodies.positions)
+                                        }));
+                let mut pulls = vec![Pull::default(); len];
+                for (_, partial) in partials {
+                    for (pull, p) in pulls.iter_mut().zip(partial) {
+                        pull.combine(&p);
+                    }
+                }
+                pulls
+            }
+            // the tree is built once, walking it is independent for every body
+            ForceMode::BarnesHut { theta } => {
+                let tree = match QuadTree::build(masses, positions) {
+                    Some(tree) => Arc::new(tree),
+                    None => return vec![Pull::default(); len],
+                };
+                pool.run_ordered(len,
+                                 chunks,
+                                 Arc::new(move |start: usize, end: usize| {
+                                     let bodies = bodies.read().unwrap();
+                                     (start..end)
+                                         .map(|i| {
+                                             tree.pull_on(i,
+                                                          &bodies.masses,
+                                                          &bodies.positions,
+                                                          theta)
+                                         })
+                                         .collect()
+                                 }))
+            }
+        }
+    }
+}
+
+// pulls from every pair whose first body is in start..end, worked out once per pair
+// and applied to both
+fn pair_pulls(start: usize, end: usize, masses: &[f64], positions: &[(f64, f64)]) -> Vec<Pull> {
+    let mut pulls = vec![Pull::default(); positions.len()];
+    for i in start..end {
+        for j in i + 1..positions.len() {
+            let dx = positions[j].0 - positions[i].0;
+            let dy = positions[j].1 - positions[i].1;
+            let distance_squared = dx * dx + dy * dy;
+            if distance_squared == 0. {
+                continue;
+            }
+            let distance_cubed = distance_squared * distance_squared.sqrt();
+            let factor = G / distance_cubed;
+            pulls[i].acceleration.0 += factor * masses[j] * dx;
+            pulls[i].acceleration.1 += factor * masses[j] * dy;
+            pulls[j].acceleration.0 -= factor * masses[i] * dx;
+            pulls[j].acceleration.1 -= factor * masses[i] * dy;
+            pulls[i].consider(j, distance_cubed, masses[i] + masses[j]);
+            pulls[j].consider(i, distance_cubed, masses[i] + masses[j]);
+        }
+    }
+    pulls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Forces;
+    use barneshut::{ForceMode, DEFAULT_THETA};
+    use kinematics::pulls_at;
+
+    // bodies spread out on a spiral with a few different masses
+    fn bodies(count: usize) -> (Vec<f64>, Vec<(f64, f64)>) {
+        let masses = (0..count).map(|i| 1e12 * (1 + i % 7) as f64).collect();
+        let positions = (0..count)
+            .map(|i| {
+                let (radius, angle) = (100. * (i as f64).sqrt(), 2.399963 * i as f64);
+                (radius * angle.cos(), radius * angle.sin())
+            })
+            .collect();
+        (masses, positions)
+    }
+
+    #[test]
+    fn deterministic_direct_matches_serial() {
+        let (masses, positions) = bodies(300);
+        let serial = pulls_at(&masses, &positions);
+        for threads in 1..9 {
+            let forces = Forces::new(ForceMode::Direct, threads, true);
+            assert!(forces.pulls(&masses, &positions) == serial,
+                    "{} threads",
+                    threads);
+        }
+    }
+
+    #[test]
+    fn deterministic_barnes_hut_matches_serial() {
+        let (masses, positions) = bodies(300);
+        let mode = ForceMode::BarnesHut { theta: DEFAULT_THETA };
+        let serial = mode.pulls(&masses, &positions);
+        for threads in 1..9 {
+            let forces = Forces::new(mode, threads, true);
+            assert!(forces.pulls(&masses, &positions) == serial,
+                    "{} threads",
+                    threads);
+        }
+    }
+
+    // the error of treating cells as single masses grows with theta squared
+    #[test]
+    fn barnes_hut_close_to_direct() {
+        let (masses, positions) = bodies(300);
+        let direct = pulls_at(&masses, &positions);
+        let size: f64 = direct.iter()
+            .map(|p| p.acceleration.0.powi(2) + p.acceleration.1.powi(2))
+            .sum();
+        for &theta in [0., 0.1, 0.3, 0.5, 1.].iter() {
+            let mode = ForceMode::BarnesHut { theta: theta };
+            let error: f64 = mode.pulls(&masses, &positions)
+                .iter()
+                .zip(direct.iter())
+                .map(|(p, q)| {
+                    (p.acceleration.0 - q.acceleration.0).powi(2) +
+                    (p.acceleration.1 - q.acceleration.1).powi(2)
+                })
+                .sum();
+            let relative = (error / size).sqrt();
+            assert!(relative <= 1e-12 + 0.5 * theta * theta,
+                    "theta {} is {} off",
+                    theta,
+                    relative);
+        }
+    }
+
+    #[test]
+    fn nondeterministic_direct_is_close() {
+        let (masses, positions) = bodies(300);
+        let serial = pulls_at(&masses, &positions);
+        // forces can nearly cancel out, so compare against the biggest one
+        let scale = serial.iter()
+            .fold(0., |s: f64, p| s.max(p.acceleration.0.abs()).max(p.acceleration.1.abs()));
+        let forces = Forces::new(ForceMode::Direct, 4, false);
+        for (p, q) in forces.pulls(&masses, &positions).iter().zip(serial.iter()) {
+            let (a, b) = (p.acceleration, q.acceleration);
+            assert!((a.0 - b.0).abs() <= 1e-9 * scale && (a.1 - b.1).abs() <= 1e-9 * scale);
+            assert_eq!(p.free_fall().map(|f| f.0), q.free_fall().map(|f| f.0));
+        }
+    }
+}
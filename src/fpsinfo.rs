@@ -1,6 +1,8 @@
 use time::precise_time_s;
 
 pub const DEFAULT_FPS: f64 = 60.;
+// milliseconds between ui iterations, about twice per frame at the default fps
+pub const UI_ITERATION_TIME: u32 = 8;
 
 #[derive(Clone)]
 pub struct FpsInfo {
@@ -23,7 +25,48 @@ impl FpsInfo {
         precise_time_s() >= self.next_update
     }
 
+    // keeps to a steady schedule, unless a whole frame was missed
     pub fn update_time(&mut self) {
-        self.next_update = precise_time_s() + self.update_time
+        let now = precise_time_s();
+        self.next_update += self.update_time;
+        if self.next_update < now {
+            self.next_update = now + self.update_time;
+        }
+    }
+}
+
+// measures how fast something is counted up, averaged over roughly a second
+pub struct RateMeter {
+    last_time: f64,
+    last_count: f64,
+    rate: f64,
+}
+
+impl Default for RateMeter {
+    fn default() -> RateMeter {
+        RateMeter {
+            last_time: precise_time_s(),
+            last_count: 0.,
+            rate: 0.,
+        }
+    }
+}
+
+impl RateMeter {
+    pub fn update(&mut self, count: f64) {
+        let now = precise_time_s();
+        if count < self.last_count {
+            // the counter was reset
+            self.last_count = count;
+            self.last_time = now;
+        } else if now - self.last_time >= 1. {
+            self.rate = (count - self.last_count) / (now - self.last_time);
+            self.last_count = count;
+            self.last_time = now;
+        }
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
     }
 }
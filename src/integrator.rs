@@ -0,0 +1,244 @@
+use physics_sim::{Object, Point, Vector};
+use kinematics::{velocity, vector_from_components, pair_time_scale};
+use forces::Forces;
+use collisions::touching_groups;
+
+// how the objects are moved forward in time
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Integrator {
+    // whatever physics_sim does itself
+    Library,
+    Euler,
+    SemiImplicitEuler,
+    // kick-drift-kick velocity verlet
+    Leapfrog,
+    RungeKutta4,
+    // fourth order symplectic
+    Yoshida4,
+}
+
+const INTEGRATORS: [Integrator; 6] = [Integrator::Library,
+                                      Integrator::Euler,
+                                      Integrator::SemiImplicitEuler,
+                                      Integrator::Leapfrog,
+                                      Integrator::RungeKutta4,
+                                      Integrator::Yoshida4];
+
+impl Default for Integrator {
+    fn default() -> Integrator {
+        Integrator::Library
+    }
+}
+
+impl Integrator {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Integrator::Library => "library",
+            Integrator::Euler => "euler",
+            Integrator::SemiImplicitEuler => "semi-implicit-euler",
+            Integrator::Leapfrog => "leapfrog",
+            Integrator::RungeKutta4 => "rk4",
+            Integrator::Yoshida4 => "yoshida4",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Integrator> {
+        INTEGRATORS.iter().cloned().find(|i| i.name() == name)
+    }
+
+    pub fn next(&self) -> Integrator {
+        let position = INTEGRATORS.iter().position(|i| i == self).unwrap_or(0);
+        INTEGRATORS[(position + 1) % INTEGRATORS.len()]
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        INTEGRATORS.iter().map(|i| i.name()).collect()
+    }
+}
+
+// the object state integrators work on, split out of the objects
+pub struct State {
+    pub masses: Vec<f64>,
+    pub radii: Vec<f64>,
+    pub positions: Vec<(f64, f64)>,
+    pub velocities: Vec<(f64, f64)>,
+    // shortest time scale seen by the last force evaluation, from each object and
+    // the one it would fall together with soonest, so it costs no extra pass
+    pub time_scale: Option<f64>,
+    // accelerations leapfrog worked out at the end of its last step, which are the
+    // ones the next step starts with as long as nothing merged in between
+    start_accelerations: Option<Vec<(f64, f64)>>,
+}
+
+impl State {
+    pub fn from_objects(objects: &[Object]) -> State {
+        State {
+            masses: objects.iter().map(|o| o.mass()).collect(),
+            radii: objects.iter().map(|o| o.radius()).collect(),
+            positions: objects.iter().map(|o| (o.position().x, o.position().y)).collect(),
+            velocities: objects.iter().map(velocity).collect(),
+            time_scale: None,
+            start_accelerations: None,
+        }
+    }
+
+    pub fn to_objects(&self) -> Vec<Object> {
+        (0..self.masses.len())
+            .map(|i| {
+                let ((x, y), (vx, vy)) = (self.positions[i], self.velocities[i]);
+                Object::new(self.masses[i], vector_from_components(vx, vy), Point::new(x, y))
+            })
+            .collect()
+    }
+
+    fn accelerations(&mut self, forces: &Forces) -> Vec<(f64, f64)> {
+        let pulls = forces.pulls(&self.masses, &self.positions);
+        let mut shortest: Option<f64> = None;
+        for (i, pull) in pulls.iter().enumerate() {
+            if let Some((j, free_fall)) = pull.free_fall() {
+                let (a, b) = (self.positions[i], self.positions[j]);
+                let (va, vb) = (self.velocities[i], self.velocities[j]);
+                let time = pair_time_scale(free_fall,
+                                           (b.0 - a.0, b.1 - a.1),
+                                           (vb.0 - va.0, vb.1 - va.1));
+                shortest = Some(shortest.map_or(time, |s: f64| s.min(time)));
+            }
+        }
+        self.time_scale = shortest;
+        pulls.into_iter().map(|p| p.acceleration).collect()
+    }
+
+    fn drift(&mut self, dt: f64) {
+        for (p, v) in self.positions.iter_mut().zip(self.velocities.iter()) {
+            p.0 += v.0 * dt;
+            p.1 += v.1 * dt;
+        }
+    }
+
+    fn kick(&mut self, accelerations: &[(f64, f64)], dt: f64) {
+        for (v, a) in self.velocities.iter_mut().zip(accelerations.iter()) {
+            v.0 += a.0 * dt;
+            v.1 += a.1 * dt;
+        }
+    }
+
+    // moves the state forward by dt, not used for the library integrator
+    pub fn step(&mut self, integrator: Integrator, forces: &Forces, dt: f64) {
+        let start_accelerations = self.start_accelerations.take();
+        match integrator {
+            // the library integrator never gets here, it's treated as euler
+            Integrator::Library | Integrator::Euler => {
+                let accelerations = self.accelerations(forces);
+                self.drift(dt);
+                self.kick(&accelerations, dt);
+            }
+            Integrator::SemiImplicitEuler => {
+                let accelerations = self.accelerations(forces);
+                self.kick(&accelerations, dt);
+                self.drift(dt);
+            }
+            Integrator::Leapfrog => {
+                let accelerations = match start_accelerations {
+                    Some(accelerations) => accelerations,
+                    None => self.accelerations(forces),
+                };
+                self.kick(&accelerations, dt / 2.);
+                self.drift(dt);
+                let accelerations = self.accelerations(forces);
+                self.kick(&accelerations, dt / 2.);
+                self.start_accelerations = Some(accelerations);
+            }
+            Integrator::RungeKutta4 => self.runge_kutta_step(forces, dt),
+            Integrator::Yoshida4 => {
+                let cbrt2 = 2f64.powf(1. / 3.);
+                let w1 = 1. / (2. - cbrt2);
+                let w0 = -cbrt2 * w1;
+                let drifts = [w1 / 2., (w0 + w1) / 2., (w0 + w1) / 2., w1 / 2.];
+                let kicks = [w1, w0, w1];
+                for i in 0..3 {
+                    self.drift(drifts[i] * dt);
+                    let accelerations = self.accelerations(forces);
+                    self.kick(&accelerations, kicks[i] * dt);
+                }
+                self.drift(drifts[3] * dt);
+            }
+        }
+    }
+
+    fn runge_kutta_step(&mut self, forces: &Forces, dt: f64) {
+        let (p0, v0) = (self.positions.clone(), self.velocities.clone());
+        // derivatives of position and velocity at the state offset by the previous stage
+        let mut stages: Vec<(Vec<(f64, f64)>, Vec<(f64, f64)>)> = Vec::with_capacity(4);
+        for &fraction in [0., 0.5, 0.5, 1.].iter() {
+            if let Some(&(ref dp, ref dv)) = stages.last() {
+                for i in 0..p0.len() {
+                    self.positions[i] = (p0[i].0 + dp[i].0 * dt * fraction,
+                                         p0[i].1 + dp[i].1 * dt * fraction);
+                    self.velocities[i] = (v0[i].0 + dv[i].0 * dt * fraction,
+                                          v0[i].1 + dv[i].1 * dt * fraction);
+                }
+            }
+            stages.push((self.velocities.clone(), self.accelerations(forces)));
+        }
+        let weights = [1., 2., 2., 1.];
+        for i in 0..p0.len() {
+            let (mut dp, mut dv) = ((0., 0.), (0., 0.));
+            for (&(ref p, ref v), &w) in stages.iter().zip(weights.iter()) {
+                dp = (dp.0 + w * p[i].0, dp.1 + w * p[i].1);
+                dv = (dv.0 + w * v[i].0, dv.1 + w * v[i].1);
+            }
+            self.positions[i] = (p0[i].0 + dp.0 * dt / 6., p0[i].1 + dp.1 * dt / 6.);
+            self.velocities[i] = (v0[i].0 + dv.0 * dt / 6., v0[i].1 + dv.1 * dt / 6.);
+        }
+    }
+
+    // merges touching objects, keeping mass and momentum, until nothing touches
+    // returns the removed indices in the order they have to be removed in,
+    // the same way physics_sim reports merges
+    pub fn merge_collisions(&mut self) -> Vec<usize> {
+        let mut removed = Vec::new();
+        loop {
+            let groups = touching_groups(&self.positions, &self.radii);
+            if groups.is_empty() {
+                return removed;
+            }
+            self.start_accelerations = None;
+            let mut keep = vec![true; self.masses.len()];
+            for group in groups {
+                for &from in &group[1..] {
+                    self.absorb(group[0], from);
+                    keep[from] = false;
+                }
+                // let physics_sim decide how big the merged object is
+                let mass = self.masses[group[0]];
+                self.radii[group[0]] = Object::new(mass, Vector::default(), Point::new(0., 0.))
+                    .radius();
+            }
+            // highest first so the indices are still right when they're removed one by one
+            removed.extend((0..keep.len()).rev().filter(|&i| !keep[i]));
+            retain(&mut self.masses, &keep);
+            retain(&mut self.radii, &keep);
+            retain(&mut self.positions, &keep);
+            retain(&mut self.velocities, &keep);
+        }
+    }
+
+    // adds one object's mass and momentum to another, leaving it to be removed
+    fn absorb(&mut self, into: usize, from: usize) {
+        let (m1, m2) = (self.masses[into], self.masses[from]);
+        let (p1, p2) = (self.positions[into], self.positions[from]);
+        let (v1, v2) = (self.velocities[into], self.velocities[from]);
+        let mass = m1 + m2;
+        self.masses[into] = mass;
+        self.positions[into] = ((m1 * p1.0 + m2 * p2.0) / mass, (m1 * p1.1 + m2 * p2.1) / mass);
+        self.velocities[into] = ((m1 * v1.0 + m2 * v2.0) / mass, (m1 * v1.1 + m2 * v2.1) / mass);
+    }
+}
+
+fn retain<T>(values: &mut Vec<T>, keep: &[bool]) {
+    let mut index = 0;
+    values.retain(|_| {
+        index += 1;
+        keep[index - 1]
+    });
+}
@@ -0,0 +1,161 @@
+use physics_sim::{Object, Vector};
+
+// same gravitational constant physics_sim uses for its force calculations, the
+// coloruniverse tests check our integrators follow the same orbits as its own
+pub const G: f64 = 6.67408e-11;
+
+pub fn velocity(object: &Object) -> (f64, f64) {
+    let velocity = object.velocity();
+    (velocity.x, velocity.y)
+}
+
+pub fn relative_state(object: &Object, primary: &Object) -> ((f64, f64), (f64, f64)) {
+    let (vx, vy) = velocity(object);
+    let (pvx, pvy) = velocity(primary);
+    ((object.position().x - primary.position().x, object.position().y - primary.position().y),
+     (vx - pvx, vy - pvy))
+}
+
+// physics_sim vectors are constructed from a magnitude and an angle
+pub fn vector_from_components(x: f64, y: f64) -> Vector {
+    Vector::new((x * x + y * y).sqrt(), y.atan2(x))
+}
+
+// net gravitational acceleration on every object from all of the others
+pub fn accelerations(objects: &[Object]) -> Vec<(f64, f64)> {
+    let masses: Vec<f64> = objects.iter().map(|o| o.mass()).collect();
+    let positions: Vec<(f64, f64)> =
+        objects.iter().map(|o| (o.position().x, o.position().y)).collect();
+    accelerations_at(&masses, &positions)
+}
+
+pub fn accelerations_at(masses: &[f64], positions: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    pulls_at(masses, positions).iter().map(|p| p.acceleration).collect()
+}
+
+pub fn pulls_at(masses: &[f64], positions: &[(f64, f64)]) -> Vec<Pull> {
+    (0..positions.len()).map(|i| pull_on(i, masses, positions)).collect()
+}
+
+// what an object feels from the others
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Pull {
+    pub acceleration: (f64, f64),
+    // the object it would fall together with soonest, with their distance cubed and
+    // combined mass
+    pub closest: Option<(usize, f64, f64)>,
+}
+
+impl Default for Pull {
+    fn default() -> Pull {
+        Pull {
+            acceleration: (0., 0.),
+            closest: None,
+        }
+    }
+}
+
+impl Pull {
+    // keeps the other object if they'd fall together sooner than with the closest one
+    // so far, the free fall time goes with the square root of distance cubed over mass
+    pub fn consider(&mut self, other: usize, distance_cubed: f64, mass: f64) {
+        let sooner = match self.closest {
+            Some((_, d, m)) => distance_cubed * m < d * mass,
+            None => mass > 0.,
+        };
+        if sooner {
+            self.closest = Some((other, distance_cubed, mass));
+        }
+    }
+
+    // adds the acceleration of another pull on the same object
+    pub fn combine(&mut self, other: &Pull) {
+        self.acceleration.0 += other.acceleration.0;
+        self.acceleration.1 += other.acceleration.1;
+        if let Some((j, distance_cubed, mass)) = other.closest {
+            self.consider(j, distance_cubed, mass);
+        }
+    }
+
+    // the closest object and the time to fall together with it
+    pub fn free_fall(&self) -> Option<(usize, f64)> {
+        self.closest.map(|(j, distance_cubed, mass)| (j, (distance_cubed / (G * mass)).sqrt()))
+    }
+}
+
+// summed in index order, so it comes out the same whichever thread works it out
+pub fn pull_on(i: usize, masses: &[f64], positions: &[(f64, f64)]) -> Pull {
+    let a = positions[i];
+    let mut pull = Pull::default();
+    for (j, b) in positions.iter().enumerate() {
+        let dx = b.0 - a.0;
+        let dy = b.1 - a.1;
+        let distance_squared = dx * dx + dy * dy;
+        if i == j || distance_squared == 0. {
+            continue;
+        }
+        let distance_cubed = distance_squared * distance_squared.sqrt();
+        let factor = G * masses[j] / distance_cubed;
+        pull.acceleration.0 += factor * dx;
+        pull.acceleration.1 += factor * dy;
+        pull.consider(j, distance_cubed, masses[i] + masses[j]);
+    }
+    pull
+}
+
+// kinetic plus gravitational potential energy
+pub fn total_energy(objects: &[Object]) -> f64 {
+    let mut energy = 0.;
+    for (i, a) in objects.iter().enumerate() {
+        let (vx, vy) = velocity(a);
+        energy += 0.5 * a.mass() * (vx * vx + vy * vy);
+        for b in objects.iter().skip(i + 1) {
+            let distance = a.position().distance_to(b.position());
+            if distance > 0. {
+                energy -= G * a.mass() * b.mass() / distance;
+            }
+        }
+    }
+    energy
+}
+
+// shortest time scale any pair of objects changes over: the time to fall together
+// and, for approaching pairs, the time until their centers meet
+// None with fewer than two objects
+// goes through every pair, our own integrators get it from the force calculation instead
+pub fn shortest_time_scale(objects: &[Object]) -> Option<f64> {
+    let mut shortest: Option<f64> = None;
+    for (i, a) in objects.iter().enumerate() {
+        for b in objects.iter().skip(i + 1) {
+            let dx = b.position().x - a.position().x;
+            let dy = b.position().y - a.position().y;
+            let distance = (dx * dx + dy * dy).sqrt();
+            let mass = a.mass() + b.mass();
+            if distance == 0. || mass <= 0. {
+                continue;
+            }
+            let free_fall = (distance.powi(3) / (G * mass)).sqrt();
+            let (avx, avy) = velocity(a);
+            let (bvx, bvy) = velocity(b);
+            let time = pair_time_scale(free_fall, (dx, dy), (bvx - avx, bvy - avy));
+            shortest = Some(shortest.map_or(time, |s: f64| s.min(time)));
+        }
+    }
+    shortest
+}
+
+// the free fall time of a pair, or the time until their centers meet if they're
+// approaching each other faster than that
+pub fn pair_time_scale(free_fall: f64,
+                       offset: (f64, f64),
+                       relative_velocity: (f64, f64))
+                       -> f64 {
+    let distance = (offset.0 * offset.0 + offset.1 * offset.1).sqrt();
+    // rate the distance is shrinking at
+    let closing = -(relative_velocity.0 * offset.0 + relative_velocity.1 * offset.1) / distance;
+    if closing > 0. {
+        free_fall.min(distance / closing)
+    } else {
+        free_fall
+    }
+}
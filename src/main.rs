@@ -14,20 +14,40 @@ mod fpsinfo;
 mod color;
 mod updater;
 mod coloruniverse;
+mod trails;
 mod iteration_result;
 mod input;
 mod draw;
+mod kinematics;
+mod orbit;
+mod integrator;
+mod barneshut;
+mod collisions;
+mod threadpool;
+mod forces;
 
+use std::path::PathBuf;
 use gtk::prelude::*;
 use ui::Ui;
 use iteration_result::IterationResult;
+use fpsinfo::UI_ITERATION_TIME;
+use updater::UpdateSettings;
+use integrator::Integrator;
+use barneshut::{ForceMode, DEFAULT_THETA, MAX_THETA};
 
 fn main() {
     initialize_gtk();
 
-    let mut ui = Ui::initialize();
+    let (update_settings, scenario) = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            println!("{}", message);
+            return;
+        }
+    };
+    let mut ui = Ui::initialize(update_settings, scenario.as_ref().map(|s| s.as_path()));
 
-    gtk::timeout_add(30, move || {
+    gtk::timeout_add(UI_ITERATION_TIME, move || {
         let mut continue_state = true;
         match ui.iterate() {
             IterationResult::Ok => {}
@@ -45,6 +65,67 @@ fn main() {
     gtk::main();
 }
 
+// --integrator=<name>: how the simulation is stepped
+// --forces=direct|barnes-hut: how forces are worked out
+// --theta=<angle>: barnes-hut opening angle
+// --threads=<count>: threads forces are worked out on, unused by the library integrator
+// --nondeterministic: faster threaded forces that depend on the thread count
+// --scenario=<path>: the scenario being looked at, view bookmarks are kept next to it
+fn parse_args() -> Result<(UpdateSettings, Option<PathBuf>), String> {
+    let mut settings = UpdateSettings::default();
+    let mut scenario = None;
+    for arg in ::std::env::args().skip(1) {
+        if arg.starts_with("--integrator=") {
+            let name = &arg["--integrator=".len()..];
+            match Integrator::from_name(name) {
+                Some(integrator) => settings.set_integrator(integrator),
+                None => {
+                    return Err(format!("unknown integrator {}, expected one of: {}",
+                                       name,
+                                       Integrator::names().join(", ")))
+                }
+            }
+        } else if arg == "--forces=direct" {
+            settings.set_forces(ForceMode::Direct);
+        } else if arg == "--forces=barnes-hut" {
+            set_forces(&mut settings, ForceMode::BarnesHut { theta: DEFAULT_THETA });
+        } else if arg.starts_with("--theta=") {
+            let theta = match arg["--theta=".len()..].parse::<f64>() {
+                Ok(theta) if theta >= 0. && theta <= MAX_THETA => theta,
+                Ok(theta) => {
+                    return Err(format!("theta {} is outside of 0 - {}", theta, MAX_THETA))
+                }
+                Err(e) => return Err(format!("invalid theta: {}", e)),
+            };
+            set_forces(&mut settings, ForceMode::BarnesHut { theta: theta });
+        } else if arg.starts_with("--threads=") {
+            match arg["--threads=".len()..].parse() {
+                Ok(threads) => settings.set_threads(threads),
+                Err(e) => return Err(format!("invalid thread count: {}", e)),
+            }
+        } else if arg == "--nondeterministic" {
+            settings.set_deterministic(false);
+        } else if arg.starts_with("--scenario=") {
+            scenario = Some(PathBuf::from(&arg["--scenario=".len()..]));
+        } else {
+            return Err(format!("unknown argument {}", arg));
+        }
+    }
+    if let (Integrator::Library, ForceMode::BarnesHut { .. }) = (settings.integrator(),
+                                                                   settings.forces()) {
+        return Err("the library integrator can't use barnes-hut forces".to_string());
+    }
+    Ok((settings, scenario))
+}
+
+fn set_forces(settings: &mut UpdateSettings, forces: ForceMode) {
+    if settings.set_forces(forces) {
+        println!("using the {} integrator, the library one can't use {}",
+                 settings.integrator().name(),
+                 forces.describe());
+    }
+}
+
 fn initialize_gtk() {
     if gtk::init().is_err() {
         println!("Failed to initialize GTK.");
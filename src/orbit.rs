@@ -0,0 +1,121 @@
+use physics_sim::Object;
+use kinematics::{G, relative_state};
+use std::f64::consts::PI;
+
+// osculating elements of a two body orbit, relative to the primary
+pub struct OrbitalElements {
+    pub semi_major_axis: f64,
+    pub eccentricity: f64,
+    pub periapsis: f64,
+    // None for parabolic and hyperbolic orbits
+    pub apoapsis: Option<f64>,
+    // angle of the periapsis from the positive x axis
+    pub argument_of_periapsis: f64,
+    pub period: Option<f64>,
+    // semi-latus rectum, used for drawing the conic
+    pub semi_latus_rectum: f64,
+}
+
+impl OrbitalElements {
+    pub fn from_objects(object: &Object, primary: &Object) -> Option<OrbitalElements> {
+        let ((rx, ry), (vx, vy)) = relative_state(object, primary);
+        OrbitalElements::from_state(G * (primary.mass() + object.mass()), rx, ry, vx, vy)
+    }
+
+    pub fn from_state(mu: f64, rx: f64, ry: f64, vx: f64, vy: f64) -> Option<OrbitalElements> {
+        let r = (rx * rx + ry * ry).sqrt();
+        let v2 = vx * vx + vy * vy;
+        if r == 0. || mu <= 0. {
+            return None;
+        }
+        let h = rx * vy - ry * vx;
+        let r_dot_v = rx * vx + ry * vy;
+        let ex = ((v2 - mu / r) * rx - r_dot_v * vx) / mu;
+        let ey = ((v2 - mu / r) * ry - r_dot_v * vy) / mu;
+        let eccentricity = (ex * ex + ey * ey).sqrt();
+        let energy = v2 / 2. - mu / r;
+        let semi_latus_rectum = h * h / mu;
+
+        let semi_major_axis = if energy == 0. {
+            ::std::f64::INFINITY
+        } else {
+            -mu / (2. * energy)
+        };
+        let (apoapsis, period) = if eccentricity < 1. {
+            (Some(semi_major_axis * (1. + eccentricity)),
+             Some(2. * PI * (semi_major_axis.powi(3) / mu).sqrt()))
+        } else {
+            (None, None)
+        };
+        // a circular orbit has no defined periapsis, so just use the current position
+        let argument_of_periapsis = if eccentricity > 1e-9 {
+            ey.atan2(ex)
+        } else {
+            ry.atan2(rx)
+        };
+
+        Some(OrbitalElements {
+            semi_major_axis: semi_major_axis,
+            eccentricity: eccentricity,
+            periapsis: semi_latus_rectum / (1. + eccentricity),
+            apoapsis: apoapsis,
+            argument_of_periapsis: argument_of_periapsis,
+            period: period,
+            semi_latus_rectum: semi_latus_rectum,
+        })
+    }
+
+    pub fn is_bound(&self) -> bool {
+        self.eccentricity < 1.
+    }
+
+    // distance from the primary at the given true anomaly
+    pub fn radius_at(&self, true_anomaly: f64) -> f64 {
+        self.semi_latus_rectum / (1. + self.eccentricity * true_anomaly.cos())
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![format!("semi-major axis: {:.1}", self.semi_major_axis),
+                             format!("eccentricity: {:.4}", self.eccentricity),
+                             format!("periapsis: {:.1}", self.periapsis)];
+        match self.apoapsis {
+            Some(apoapsis) => lines.push(format!("apoapsis: {:.1}", apoapsis)),
+            None => lines.push("apoapsis: unbound".to_string()),
+        }
+        lines.push(format!("arg. of periapsis: {:.1}°",
+                           self.argument_of_periapsis.to_degrees()));
+        match self.period {
+            Some(period) => lines.push(format!("period: {:.1}", period)),
+            None => lines.push("period: unbound".to_string()),
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OrbitalElements;
+
+    #[test]
+    fn circular_orbit() {
+        // v = sqrt(mu / r)
+        let elements = OrbitalElements::from_state(4., 1., 0., 0., 2.).unwrap();
+        assert!(elements.eccentricity < 1e-12);
+        assert!((elements.semi_major_axis - 1.).abs() < 1e-12);
+        assert!(elements.is_bound());
+    }
+
+    #[test]
+    fn escape_velocity_is_unbound() {
+        // just above v = sqrt(2 mu / r)
+        let elements = OrbitalElements::from_state(1., 0., 2., -1.01, 0.).unwrap();
+        assert!(!elements.is_bound());
+        assert!(elements.apoapsis.is_none());
+        assert!(elements.period.is_none());
+    }
+
+    #[test]
+    fn no_elements_at_the_primary() {
+        assert!(OrbitalElements::from_state(1., 0., 0., 1., 0.).is_none());
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::panic::{self, AssertUnwindSafe};
+
+// works something out for every item in the range start..end
+pub type Work<T> = Arc<Fn(usize, usize) -> Vec<T> + Send + Sync>;
+
+// runs a chunk of work given its index, start and end and sends the results back
+type ChunkRunner = Arc<Fn(usize, usize, usize) + Send + Sync>;
+
+struct Job {
+    run: ChunkRunner,
+    chunk: usize,
+    start: usize,
+    end: usize,
+}
+
+// a fixed number of threads that split up work given to them
+pub struct ThreadPool {
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    pub fn new(threads: usize) -> ThreadPool {
+        let (jobs, job_recv) = channel::<Job>();
+        let job_recv = Arc::new(Mutex::new(job_recv));
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let job_recv = job_recv.clone();
+                thread::spawn(move || worker(job_recv))
+            })
+            .collect();
+        ThreadPool {
+            jobs: Some(jobs),
+            workers: workers,
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    // splits 0..len into chunks, runs the work on them and returns the results
+    // in the order they finished, along with the chunk they came from
+    // panics if the work panicked on any of the chunks
+    pub fn run<T>(&self, len: usize, chunks: usize, work: Work<T>) -> Vec<(usize, Vec<T>)>
+        where T: Send + 'static
+    {
+        let chunks = chunks.max(1).min(len.max(1));
+        let (results, result_recv) = channel();
+        let results = Mutex::new(results);
+        let run: ChunkRunner = Arc::new(move |chunk: usize, start: usize, end: usize| {
+            let result = work(start, end);
+            // the pool may have stopped waiting, which is fine
+            let _ = results.lock().unwrap().send((chunk, result));
+        });
+        for chunk in 0..chunks {
+            let job = Job {
+                run: run.clone(),
+                chunk: chunk,
+                start: len * chunk / chunks,
+                end: len * (chunk + 1) / chunks,
+            };
+            self.jobs.as_ref().unwrap().send(job).unwrap();
+        }
+        // the jobs hold the only senders now, so a chunk that never sends anything
+        // ends the results early instead of leaving this waiting
+        drop(run);
+        let results: Vec<(usize, Vec<T>)> = result_recv.iter().take(chunks).collect();
+        if results.len() != chunks {
+            panic!("{} of {} chunks of work didn't finish", chunks - results.len(), chunks);
+        }
+        results
+    }
+
+    // like run, but the results are put back together in order
+    pub fn run_ordered<T>(&self, len: usize, chunks: usize, work: Work<T>) -> Vec<T>
+        where T: Send + 'static
+    {
+        let mut results = self.run(len, chunks, work);
+        results.sort_by_key(|&(chunk, _)| chunk);
+        results.into_iter().flat_map(|(_, result)| result).collect()
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // closing the channel lets every worker finish
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            // workers catch panics from their jobs, and a failed join can't be done
+            // anything about while dropping anyway
+            let _ = worker.join();
+        }
+    }
+}
+
+fn worker(job_recv: Arc<Mutex<Receiver<Job>>>) {
+    loop {
+        let job = match job_recv.lock().unwrap().recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        // the job is dropped without sending a result if it panics, which run notices,
+        // and the worker stays around for the next one
+        let run = job.run;
+        let (chunk, start, end) = (job.chunk, job.start, job.end);
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| run(chunk, start, end)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ThreadPool;
+    use std::sync::Arc;
+
+    #[test]
+    fn ordered_results() {
+        let pool = ThreadPool::new(3);
+        let squares = pool.run_ordered(100,
+                                       7,
+                                       Arc::new(|start: usize, end: usize| {
+                                           (start..end).map(|i| i * i).collect()
+                                       }));
+        assert_eq!(squares, (0..100).map(|i| i * i).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "didn't finish")]
+    fn panicking_work_is_noticed() {
+        let pool = ThreadPool::new(2);
+        pool.run(10,
+                 5,
+                 Arc::new(|start: usize, end: usize| {
+                     if start == 4 {
+                         panic!("chunk failed");
+                     }
+                     (start..end).collect::<Vec<usize>>()
+                 }));
+    }
+}
@@ -0,0 +1,403 @@
+use physics_sim::Point;
+use draw::Bounds;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Index;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+// hard limit on stored points per path, whatever the trail settings are
+pub const MAX_POSITIONS_STORED: usize = 100000;
+const ADD_POSITION_MULTIPLE: usize = 2;
+
+// how much of an object's path is kept
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TrailLimit {
+    Points(usize),
+    // simulated time
+    Time(f64),
+}
+
+impl TrailLimit {
+    fn capacity(&self) -> usize {
+        match *self {
+            TrailLimit::Points(points) => points.min(MAX_POSITIONS_STORED),
+            TrailLimit::Time(_) => MAX_POSITIONS_STORED,
+        }
+    }
+
+    // scales the length, keeping at least one point
+    pub fn multiply(&self, factor: f64) -> TrailLimit {
+        match *self {
+            TrailLimit::Points(points) => {
+                TrailLimit::Points(((points as f64 * factor) as usize)
+                    .max(1)
+                    .min(MAX_POSITIONS_STORED))
+            }
+            TrailLimit::Time(time) => TrailLimit::Time(time * factor),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TrailSettings {
+    pub limit: TrailLimit,
+    // updates between each stored point
+    pub sample_interval: usize,
+}
+
+impl Default for TrailSettings {
+    fn default() -> TrailSettings {
+        TrailSettings {
+            limit: TrailLimit::Points(MAX_POSITIONS_STORED),
+            sample_interval: ADD_POSITION_MULTIPLE,
+        }
+    }
+}
+
+// every object's path, keyed by object id so it doesn't matter which copy of the
+// universe is looking at it
+pub struct Trails {
+    paths: HashMap<usize, CapVecDeque>,
+    barycenter: CapVecDeque,
+    settings: TrailSettings,
+    // overrides the global limit for single objects
+    limits: HashMap<usize, TrailLimit>,
+    // time of the newest sample
+    latest_time: f64,
+}
+
+impl Default for Trails {
+    fn default() -> Trails {
+        Trails {
+            paths: HashMap::new(),
+            barycenter: CapVecDeque::with_capacity(MAX_POSITIONS_STORED),
+            settings: TrailSettings::default(),
+            limits: HashMap::new(),
+            latest_time: 0.,
+        }
+    }
+}
+
+impl Trails {
+    pub fn path(&self, id: usize) -> Option<&CapVecDeque> {
+        self.paths.get(&id)
+    }
+
+    // kept at full length since per-object trails can be longer than the global one
+    pub fn barycenter(&self) -> &CapVecDeque {
+        &self.barycenter
+    }
+
+    pub fn settings(&self) -> &TrailSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: TrailSettings) {
+        self.settings = settings;
+        let ids: Vec<usize> = self.paths.keys().cloned().collect();
+        for id in ids {
+            self.apply_limit(id);
+        }
+    }
+
+    pub fn limit(&self, id: usize) -> TrailLimit {
+        self.limits.get(&id).cloned().unwrap_or(self.settings.limit)
+    }
+
+    // None goes back to the global limit
+    pub fn set_limit(&mut self, id: usize, limit: Option<TrailLimit>) {
+        match limit {
+            Some(limit) => self.limits.insert(id, limit),
+            None => self.limits.remove(&id),
+        };
+        self.apply_limit(id);
+    }
+
+    fn apply_limit(&mut self, id: usize) {
+        let (limit, now) = (self.limit(id), self.latest_time);
+        if let Some(path) = self.paths.get_mut(&id) {
+            path.limit(limit, now);
+        }
+    }
+
+    pub fn add_path(&mut self, id: usize) {
+        let capacity = self.settings.limit.capacity();
+        self.paths.insert(id, CapVecDeque::with_capacity(capacity));
+    }
+
+    pub fn remove_path(&mut self, id: usize) {
+        self.paths.remove(&id);
+        self.limits.remove(&id);
+    }
+
+    pub fn sample(&mut self, time: f64, positions: &[(usize, Point)], barycenter: Option<Point>) {
+        self.latest_time = time;
+        for &(id, pt) in positions {
+            let limit = self.limit(id);
+            if let Some(path) = self.paths.get_mut(&id) {
+                path.add_element(pt, time);
+                path.limit(limit, time);
+            }
+        }
+        if let Some(barycenter) = barycenter {
+            self.barycenter.add_element(barycenter, time);
+        }
+    }
+
+    // drops samples newer than the time, for when an older state of the universe
+    // replaces the one that made them
+    pub fn truncate_after(&mut self, time: f64) {
+        for path in self.paths.values_mut() {
+            path.truncate_after(time);
+        }
+        self.barycenter.truncate_after(time);
+        self.latest_time = self.latest_time.min(time);
+    }
+}
+
+// shared between the updater and the ui instead of being copied with every state
+#[derive(Clone, Default)]
+pub struct SharedTrails {
+    inner: Arc<RwLock<Trails>>,
+}
+
+impl SharedTrails {
+    pub fn get_trails(&self) -> RwLockReadGuard<Trails> {
+        self.inner.read().unwrap()
+    }
+
+    pub fn get_trails_mut(&self) -> RwLockWriteGuard<Trails> {
+        self.inner.write().unwrap()
+    }
+}
+
+trait CircularBuffer {
+    fn add_element(&mut self, element: Point, time: f64);
+}
+
+impl CircularBuffer for CapVecDeque {
+    fn add_element(&mut self, element: Point, time: f64) {
+        if self.len() == self.capacity() {
+            self.pop_front();
+            self.push_back(element, time);
+        } else {
+            self.push_back(element, time);
+        }
+    }
+}
+
+// ugly as hell workaround because normal vecdeque does not preserve capacity on clone
+// also keeps the simulated time each point was stored at
+pub struct CapVecDeque {
+    capacity: usize,
+    inner: VecDeque<Point>,
+    times: VecDeque<f64>,
+    // box around the stored points, kept up to date as they're added so nothing has
+    // to walk the whole path to bound it
+    // dropped points stay inside it until as many more have been dropped as are
+    // stored, then it's worked out again
+    extent: Option<Bounds>,
+    dropped: usize,
+}
+
+impl CapVecDeque {
+    pub fn new() -> CapVecDeque {
+        CapVecDeque {
+            capacity: 1,
+            inner: VecDeque::new(),
+            times: VecDeque::new(),
+            extent: None,
+            dropped: 0,
+        }
+    }
+
+    pub fn with_capacity(cap: usize) -> CapVecDeque {
+        CapVecDeque {
+            capacity: cap,
+            inner: VecDeque::with_capacity(cap),
+            times: VecDeque::with_capacity(cap),
+            extent: None,
+            dropped: 0,
+        }
+    }
+
+    // drops the oldest points until the limit is met
+    pub fn limit(&mut self, limit: TrailLimit, now: f64) {
+        self.capacity = limit.capacity();
+        while self.len() > self.capacity {
+            self.pop_front();
+        }
+        if let TrailLimit::Time(time) = limit {
+            while self.times.front().map_or(false, |&t| t < now - time) {
+                self.pop_front();
+            }
+        }
+    }
+
+    pub fn truncate_after(&mut self, time: f64) {
+        let len = self.len();
+        while self.times.back().map_or(false, |&t| t > time) {
+            self.times.pop_back();
+            self.inner.pop_back();
+        }
+        if self.len() != len {
+            self.refit_extent();
+        }
+    }
+
+    pub fn extent(&self) -> Option<&Bounds> {
+        self.extent.as_ref()
+    }
+
+    fn refit_extent(&mut self) {
+        let mut extent: Option<Bounds> = None;
+        for pt in self.inner.iter() {
+            match extent {
+                Some(ref mut e) => e.include(pt.x, pt.y, 0.),
+                None => extent = Some(Bounds::around(pt.x, pt.y, 0.)),
+            }
+        }
+        self.extent = extent;
+        self.dropped = 0;
+    }
+
+    // number of points stored at or before the time
+    pub fn len_until(&self, time: f64) -> usize {
+        let mut len = self.len();
+        while len > 0 && self.times[len - 1] > time {
+            len -= 1;
+        }
+        len
+    }
+
+    pub fn time(&self, index: usize) -> f64 {
+        self.times[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn iter(&self) -> ::std::collections::vec_deque::Iter<Point> {
+        self.inner.iter()
+    }
+
+    pub fn pop_front(&mut self) -> Option<Point> {
+        self.times.pop_front();
+        let front = self.inner.pop_front();
+        self.dropped += 1;
+        if self.dropped >= self.len() {
+            self.refit_extent();
+        }
+        front
+    }
+
+    pub fn push_back(&mut self, elem: Point, time: f64) {
+        match self.extent {
+            Some(ref mut e) => e.include(elem.x, elem.y, 0.),
+            None => self.extent = Some(Bounds::around(elem.x, elem.y, 0.)),
+        }
+        self.inner.push_back(elem);
+        self.times.push_back(time);
+    }
+
+    // the positions up to the time re-expressed relative to a frame's history,
+    // placed at the frame's current position, worked out as they're iterated over
+    // samples from outside the frame's history are left out
+    pub fn relative_to<'a>(&'a self,
+                           frame: &'a CapVecDeque,
+                           origin: &Point,
+                           until: f64)
+                           -> RelativePoints<'a> {
+        let (start, end) = match (frame.times.front(), frame.times.back()) {
+            (Some(&first), Some(&last)) => {
+                let start = self.times.iter().take_while(|&&t| t < first).count();
+                (start, self.len_until(until.min(last)).max(start))
+            }
+            _ => (0, 0),
+        };
+        RelativePoints {
+            positions: self,
+            frame: frame,
+            origin: *origin,
+            index: start,
+            end: end,
+            frame_index: 0,
+        }
+    }
+}
+
+// samples are matched up with the frame's by time, interpolating between the frame's
+pub struct RelativePoints<'a> {
+    positions: &'a CapVecDeque,
+    frame: &'a CapVecDeque,
+    origin: Point,
+    index: usize,
+    end: usize,
+    frame_index: usize,
+}
+
+impl<'a> Iterator for RelativePoints<'a> {
+    type Item = Point;
+
+    fn next(&mut self) -> Option<Point> {
+        if self.index >= self.end {
+            return None;
+        }
+        let time = self.positions.time(self.index);
+        let frame = self.frame;
+        // every sample in start..end is within the frame's history, so this stops in it
+        while frame.time(self.frame_index) < time {
+            self.frame_index += 1;
+        }
+        let j = self.frame_index;
+        let f = if frame.time(j) == time {
+            frame[j]
+        } else {
+            let (a, b) = (&frame[j - 1], &frame[j]);
+            let (t0, t1) = (frame.time(j - 1), frame.time(j));
+            let t = (time - t0) / (t1 - t0);
+            Point::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t)
+        };
+        let pt = &self.positions[self.index];
+        self.index += 1;
+        Some(Point::new(pt.x - f.x + self.origin.x, pt.y - f.y + self.origin.y))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.index;
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for RelativePoints<'a> {}
+
+impl Index<usize> for CapVecDeque {
+    type Output = Point;
+    fn index(&self, index: usize) -> &Point {
+        &self.inner[index]
+    }
+}
+
+impl Clone for CapVecDeque {
+    fn clone(&self) -> Self {
+        CapVecDeque {
+            capacity: self.capacity,
+            inner: {
+                let mut new = VecDeque::with_capacity(self.capacity);
+                new.extend(self.inner.iter().cloned());
+                new
+            },
+            times: {
+                let mut new = VecDeque::with_capacity(self.capacity);
+                new.extend(self.times.iter().cloned());
+                new
+            },
+            extent: self.extent.clone(),
+            dropped: self.dropped,
+        }
+    }
+}
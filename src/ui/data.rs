@@ -1,10 +1,15 @@
-use super::state::UiState;
-use updater::{UpdateSettings, UpdaterCommand};
+use std::path::{Path, PathBuf};
+use super::state::{UiState, NameEntry, NameTarget};
+use updater::{UpdateSettings, UpdaterCommand, LatestState, SimRate};
 use coloruniverse::ColorUniverse;
 use input::InputInfo;
 use fpsinfo::*;
-use std::sync::mpsc::{Sender, Receiver};
-use draw::DrawInfo;
+use std::sync::mpsc::Sender;
+use barneshut::ForceMode;
+use kinematics::total_energy;
+use integrator::Integrator;
+use draw::{DrawInfo, FieldOverlay, Heatmap, Minimap, ViewBookmarks, BoundsCache,
+           universe_bounds, bookmark_path};
 
 pub struct UiData {
     pub state: UiState,
@@ -12,26 +17,379 @@ pub struct UiData {
     pub fps_info: FpsInfo,
     pub draw_info: DrawInfo,
     pub input_info: InputInfo,
-    pub universe_recv: Receiver<ColorUniverse>,
+    pub latest_state: LatestState,
+    // bumped every time the ui replaces the updater's universe
+    pub generation: usize,
     pub update_settings: UpdateSettings,
     pub update_command_send: Sender<UpdaterCommand>,
+    // multiplies the default simulation rate, negative runs backwards
+    pub speed: f64,
+    pub fast_as_possible: bool,
     pub allow_mouse_movement: bool,
+    // ids of the selected object and the orbit primary
+    pub selected: Option<usize>,
+    // None means the most massive object
+    pub orbit_primary: Option<usize>,
+    // name being typed for an object or a bookmark
+    pub name_entry: Option<NameEntry>,
+    // keys pressed while naming, their releases don't go on to do anything else
+    pub name_entry_keys: Vec<u32>,
+    pub show_orbit: bool,
+    pub bookmarks: ViewBookmarks,
+    pub bookmark_path: PathBuf,
+    // message shown in the hud until it's old enough, with the time it was set
+    pub status: Option<(String, f64)>,
+    pub show_hud: bool,
+    // frames drawn and universe steps received, for the hud
+    pub frames_drawn: usize,
+    pub frame_rate: RateMeter,
+    pub step_rate: RateMeter,
+    pub sim_time_rate: RateMeter,
+    // a state arrived since the last frame was drawn
+    pub new_state: bool,
+    // frames drawn without a new state, and steps never shown because a newer state
+    // replaced them first
+    pub repeated_frames: usize,
+    pub skipped_steps: usize,
+    // energy when the universe was last set, to show how much the integrator drifts
+    pub reference_energy: Option<f64>,
+    // the energy now and when it was worked out, it's too slow to do every frame
+    pub energy: Option<(f64, f64)>,
+    pub show_minimap: bool,
+    // shared by the minimap and fitting the view
+    pub bounds: BoundsCache,
+    // the left mouse button went down on the minimap and hasn't been released
+    pub minimap_drag: bool,
+    pub heatmap: Heatmap,
+    pub field_overlay: FieldOverlay,
+    // outline the barnes-hut tree cells
+    pub show_tree: bool,
 }
 
+// seconds between working out the energy for the hud
+pub const ENERGY_SAMPLE_TIME: f64 = 0.5;
+// seconds a status message stays in the hud
+pub const STATUS_TIME: f64 = 5.;
+// speed multipliers picked between by the speed controls
+pub const SPEEDS: [f64; 4] = [0.1, 1., 10., 100.];
+
 impl UiData {
-    pub fn new(universe_recv: Receiver<ColorUniverse>,
-               update_command_send: Sender<UpdaterCommand>)
+
+    pub fn new(latest_state: LatestState,
+               update_command_send: Sender<UpdaterCommand>,
+               update_settings: UpdateSettings,
+               scenario: Option<&Path>)
                -> UiData {
+        let bookmark_path = bookmark_path(scenario);
+        let (bookmarks, status) = match ViewBookmarks::load(&bookmark_path) {
+            Ok(bookmarks) => (bookmarks, None),
+            Err(e) => {
+                (ViewBookmarks::default(),
+                 Some((format!("failed to load view bookmarks: {}", e),
+                       ::time::precise_time_s())))
+            }
+        };
         UiData {
             state: UiState::default(),
             universe: ColorUniverse::default(),
             fps_info: FpsInfo::default(),
             draw_info: DrawInfo::default(),
-            universe_recv: universe_recv,
-            update_settings: UpdateSettings::default(),
+            latest_state: latest_state,
+            generation: 0,
+            speed: 1.,
+            fast_as_possible: false,
+            update_settings: update_settings,
             update_command_send: update_command_send,
             input_info: InputInfo::default(),
             allow_mouse_movement: false,
+            selected: None,
+            orbit_primary: None,
+            name_entry: None,
+            name_entry_keys: Vec::new(),
+            show_orbit: false,
+            bookmarks: bookmarks,
+            bookmark_path: bookmark_path,
+            status: status,
+            show_hud: true,
+            frames_drawn: 0,
+            frame_rate: RateMeter::default(),
+            step_rate: RateMeter::default(),
+            sim_time_rate: RateMeter::default(),
+            new_state: false,
+            repeated_frames: 0,
+            skipped_steps: 0,
+            reference_energy: None,
+            energy: None,
+            show_minimap: true,
+            bounds: BoundsCache::default(),
+            minimap_drag: false,
+            heatmap: Heatmap::default(),
+            field_overlay: FieldOverlay::default(),
+            show_tree: false,
+        }
+    }
+
+    // index of the selected object, if it still exists
+    pub fn selected_index(&self) -> Option<usize> {
+        self.selected.and_then(|id| self.universe.index_of(id))
+    }
+
+    // index of the primary the object at index orbits, falling back to the most
+    // massive other object
+    pub fn orbit_primary(&self, index: usize) -> Option<usize> {
+        self.orbit_primary
+            .and_then(|id| self.universe.index_of(id))
+            .and_then(|primary| if primary == index { None } else { Some(primary) })
+            .or_else(|| self.universe.most_massive(Some(index)))
+    }
+
+    // sends the current universe to the updater, states it already made are ignored
+    pub fn push_universe(&mut self) {
+        self.generation += 1;
+        self.reference_energy = None;
+        self.update_command_send
+            .send(UpdaterCommand::SetUniverse(self.universe.clone(), self.generation))
+            .unwrap();
+    }
+
+    // takes the updater's newest state if there is one
+    pub fn receive_universe(&mut self) {
+        if let Some(new_universe) = self.latest_state.take(self.generation) {
+            let steps = new_universe.steps();
+            if steps > self.universe.steps() + 1 {
+                self.skipped_steps += steps - self.universe.steps() - 1;
+            }
+            self.universe = new_universe;
+            self.new_state = true;
+        }
+    }
+
+    pub fn send_update_settings(&self) {
+        self.update_command_send
+            .send(UpdaterCommand::UpdateSettings(self.update_settings.clone()))
+            .unwrap();
+    }
+
+    // switching integrators starts measuring the drift again
+    pub fn next_integrator(&mut self) {
+        let integrator = self.update_settings.integrator().next();
+        self.update_settings.set_integrator(integrator);
+        self.send_update_settings();
+        self.reference_energy = None;
+    }
+
+    pub fn set_forces(&mut self, forces: ForceMode) {
+        if self.update_settings.set_forces(forces) {
+            let message = format!("switched to the {} integrator, the library one can't use {}",
+                                  self.update_settings.integrator().name(),
+                                  forces.describe());
+            self.set_status(message);
+            self.reference_energy = None;
+        }
+        self.send_update_settings();
+    }
+
+    // threads are easy to expect to speed up everything
+    fn explain_threads(&mut self) {
+        let message = match self.update_settings.integrator() {
+            Integrator::Library => {
+                "threads only work out forces for the other integrators, tab to switch"
+            }
+            _ => "threads only work out forces, stepping and collisions stay on one thread",
+        };
+        self.set_status(message.to_string());
+    }
+
+    pub fn step_threads(&mut self, more: bool) {
+        let threads = self.update_settings.threads();
+        let threads = if more {
+            threads + 1
+        } else {
+            threads.saturating_sub(1)
+        };
+        self.update_settings.set_threads(threads);
+        self.send_update_settings();
+        self.explain_threads();
+    }
+
+    pub fn toggle_deterministic(&mut self) {
+        let deterministic = !self.update_settings.deterministic();
+        self.update_settings.set_deterministic(deterministic);
+        self.send_update_settings();
+        self.explain_threads();
+    }
+
+    pub fn sim_rate(&self) -> SimRate {
+        if self.fast_as_possible {
+            SimRate::FastAsPossible { reverse: self.speed < 0. }
+        } else {
+            SimRate::Target(self.update_settings.default_rate() * self.speed)
+        }
+    }
+
+    fn send_sim_rate(&self) {
+        self.update_command_send.send(UpdaterCommand::SetRate(self.sim_rate())).unwrap();
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+        self.send_sim_rate();
+    }
+
+    // the next speed in the list, up or down, keeping the direction
+    pub fn step_speed(&mut self, up: bool) {
+        let magnitude = self.speed.abs();
+        let next = if up {
+            SPEEDS.iter().cloned().find(|&s| s > magnitude).unwrap_or(magnitude)
+        } else {
+            SPEEDS.iter().cloned().rev().find(|&s| s < magnitude).unwrap_or(magnitude)
+        };
+        let speed = next * self.speed.signum();
+        self.set_speed(speed);
+    }
+
+    pub fn toggle_fast_as_possible(&mut self) {
+        self.fast_as_possible = !self.fast_as_possible;
+        self.send_sim_rate();
+    }
+
+    pub fn scale_trails(&mut self, factor: f64) {
+        let mut settings = self.universe.trail_settings();
+        settings.limit = settings.limit.multiply(factor);
+        self.universe.set_trail_settings(settings);
+    }
+
+    // gives the selected object its own trail length, starting from the one it has now
+    pub fn scale_selected_trail(&mut self, factor: f64) {
+        if let (Some(id), Some(index)) = (self.selected, self.selected_index()) {
+            let limit = self.universe.trail_limit(index).multiply(factor);
+            self.universe.set_trail_limit(id, Some(limit));
+        }
+    }
+
+    pub fn update_bounds(&mut self) {
+        self.bounds.update(&self.universe, &self.draw_info, self.generation);
+    }
+
+    // fits the view to every object, or only the selected one
+    pub fn fit_view(&mut self, selection_only: bool) {
+        let bounds = if selection_only {
+            match self.selected_index() {
+                Some(index) => universe_bounds(&self.universe, &self.draw_info, Some(index)),
+                None => return,
+            }
+        } else {
+            self.update_bounds();
+            self.bounds.bounds().cloned()
+        };
+        if let Some(bounds) = bounds {
+            self.draw_info.fit_bounds(&bounds);
+        }
+    }
+
+    // sets the reference energy for a new universe, and samples the current energy
+    // every so often
+    pub fn update_energy(&mut self) {
+        if self.universe.objects().is_empty() {
+            return;
+        }
+        let now = ::time::precise_time_s();
+        if self.reference_energy.is_none() {
+            let energy = total_energy(self.universe.objects());
+            self.reference_energy = Some(energy);
+            self.energy = Some((energy, now));
+        } else if self.energy.map_or(true, |(_, at)| now - at > ENERGY_SAMPLE_TIME) {
+            self.energy = Some((total_energy(self.universe.objects()), now));
+        }
+    }
+
+    pub fn set_status(&mut self, message: String) {
+        self.status = Some((message, ::time::precise_time_s()));
+    }
+
+    // the status message, if it hasn't been up for too long
+    pub fn status(&self) -> Option<&str> {
+        match self.status {
+            Some((ref message, time)) if ::time::precise_time_s() - time < STATUS_TIME => {
+                Some(message.as_str())
+            }
+            _ => None,
+        }
+    }
+
+    // stores the view under its old name, or a default one, and starts naming it
+    pub fn store_bookmark(&mut self, slot: usize) {
+        let name = self.bookmarks
+            .get(slot)
+            .map(|b| b.name.clone())
+            .unwrap_or(format!("view {}", slot + 1));
+        let bookmark = self.draw_info.bookmark(&self.universe, name.clone());
+        self.bookmarks.set(slot, bookmark);
+        self.save_bookmarks();
+        self.name_entry = Some(NameEntry {
+            target: NameTarget::Bookmark(slot),
+            text: name,
+        });
+    }
+
+    fn save_bookmarks(&mut self) {
+        if let Err(e) = self.bookmarks.save(&self.bookmark_path) {
+            let message = format!("failed to save view bookmarks: {}", e);
+            self.set_status(message);
+        }
+    }
+
+    // starts naming the selected object
+    pub fn start_naming_selected(&mut self) {
+        if let (Some(id), Some(index)) = (self.selected, self.selected_index()) {
+            let current = self.universe.name(index).unwrap_or("").to_string();
+            self.name_entry = Some(NameEntry {
+                target: NameTarget::Object(id),
+                text: current,
+            });
+        }
+    }
+
+    // gives whatever was being named its new name
+    pub fn finish_name_entry(&mut self) {
+        if let Some(entry) = self.name_entry.take() {
+            let name = entry.text.trim().to_string();
+            match entry.target {
+                NameTarget::Object(id) => {
+                    self.universe.set_name(id, name);
+                    self.push_universe();
+                }
+                // an empty name keeps the old one
+                NameTarget::Bookmark(slot) => {
+                    if !name.is_empty() {
+                        self.bookmarks.rename(slot, name);
+                        self.save_bookmarks();
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn recall_bookmark(&mut self, slot: usize) {
+        if let Some(bookmark) = self.bookmarks.get(slot) {
+            self.draw_info.recall_bookmark(&self.universe, bookmark);
+        }
+    }
+
+    pub fn minimap(&self) -> Option<Minimap> {
+        if self.show_minimap {
+            Some(Minimap::new(self.bounds.bounds(), &self.draw_info))
+        } else {
+            None
+        }
+    }
+
+    // centers the view on whatever is under the mouse in the minimap
+    pub fn recenter_from_minimap(&mut self) {
+        let (mouse_x, mouse_y) = (self.input_info.mouse_x, self.input_info.mouse_y);
+        if let Some(minimap) = self.minimap() {
+            let (x, y) = minimap.to_world(mouse_x, mouse_y);
+            self.draw_info.center_on(x, y);
         }
     }
 }
@@ -3,11 +3,11 @@ use sharedstate::SharedState;
 use physics_sim::Vector;
 use draw::*;
 use color::*;
-use std::collections::VecDeque;
-use coloruniverse::CapVecDeque;
 use super::data::UiData;
 use super::state::*;
+use super::hud::draw_hud;
 use physics_sim::*;
+use orbit::OrbitalElements;
 
 pub fn draw_handler(data: &SharedState<UiData>, ctxt: &Context) {
     let ref mut data = *data.get_state_mut();
@@ -15,12 +15,39 @@ pub fn draw_handler(data: &SharedState<UiData>, ctxt: &Context) {
     ctxt.set_operator(::cairo::Operator::Source);
     ctxt.set_source_rgb(0.0, 0.0, 0.0);
     ctxt.paint();
+    // draw the field behind everything
+    ctxt.set_operator(::cairo::Operator::Over);
+    ctxt.identity_matrix();
+    data.heatmap.draw(ctxt, data.universe.objects(), &data.draw_info);
+    data.field_overlay.draw(ctxt, data.universe.objects(), &mut data.draw_info);
     // apply the drawing info
     data.draw_info.apply(ctxt);
     // draw grid
     data.draw_info.draw_grid(ctxt);
+    if data.show_tree {
+        draw_tree(ctxt, &data.draw_info, data.universe.objects());
+    }
     // draw everything
     data.universe.draw_all(ctxt, &data.draw_info);
+    // arrows look a second ahead at the default rate, the speed new objects are placed at
+    draw_vectors(ctxt,
+                 &data.universe,
+                 &data.draw_info,
+                 data.update_settings.default_rate());
+    // draw the selection and its orbit
+    draw_selection(data, ctxt);
+    // draw the screen space overlays
+    ctxt.identity_matrix();
+    let editing = match data.name_entry {
+        Some(NameEntry { target: NameTarget::Object(ref id), ref text }) => {
+            data.universe.index_of(*id).map(|index| (index, text.as_str()))
+        }
+        _ => None,
+    };
+    draw_labels(ctxt, &data.universe, &data.draw_info, editing);
+    data.draw_info.draw_grid_labels(ctxt);
+    data.draw_info.draw_scale_bar(ctxt);
+    data.draw_info.apply(ctxt);
     // draw the mode
 
     // draw the edit information(if its in edit mode)
@@ -45,14 +72,21 @@ pub fn draw_handler(data: &SharedState<UiData>, ctxt: &Context) {
                                      radius,
                                      0.,
                                      2. * ::std::f64::consts::PI);
-                            let color = mass_to_color(mass);
+                            let color = ColorContext::new(&data.universe,
+                                                          data.draw_info.palette(),
+                                                          ObjectColor::FromMass)
+                                .mass_color(mass);
                             ctxt.set_source_rgba(color.0, color.1, color.2, 0.4);
                             ctxt.fill();
                         }
                         MouseEditState::SetVelocity(mass, center_pt) => {
                             // draw object
                             let tmp_object = Object::new(mass, Vector::default(), center_pt);
-                            tmp_object.draw(ctxt, &data.draw_info, &ObjectColor::FromMass);
+                            let color = ColorContext::new(&data.universe,
+                                                          data.draw_info.palette(),
+                                                          ObjectColor::FromMass)
+                                .mass_color(mass);
+                            tmp_object.draw(ctxt, &data.draw_info, &color);
 
                             // draw potential velocity vector
                             ctxt.new_path();
@@ -83,6 +117,74 @@ pub fn draw_handler(data: &SharedState<UiData>, ctxt: &Context) {
         _ => {}
     }
 
+    // draw the minimap
+    if let Some(minimap) = data.minimap() {
+        ctxt.identity_matrix();
+        minimap.draw(ctxt, &data.universe, &data.draw_info);
+    }
+
+    // draw the legend for the computed colors
+    ctxt.identity_matrix();
+    let context = ColorContext::new(&data.universe,
+                                    data.draw_info.palette(),
+                                    data.draw_info.color_mode().clone());
+    draw_legend(ctxt, &data.draw_info, &context);
+
+    // draw the hud over everything else
+    if data.show_hud {
+        ctxt.identity_matrix();
+        draw_hud(data, ctxt);
+    }
+
     // get ready for next fps update
     data.fps_info.update_time();
+    data.frames_drawn += 1;
+    if !data.new_state {
+        data.repeated_frames += 1;
+    }
+    data.new_state = false;
+    let frames_drawn = data.frames_drawn as f64;
+    data.frame_rate.update(frames_drawn);
+}
+
+fn draw_selection(data: &UiData, ctxt: &Context) {
+    let objects = data.universe.objects();
+    let selected = match data.selected_index() {
+        Some(index) => index,
+        None => return,
+    };
+    let object = &objects[selected];
+    ctxt.new_path();
+    ctxt.arc(object.position().x,
+             object.position().y,
+             object.radius() + data.draw_info.get_actual_width(6.),
+             0.,
+             2. * ::std::f64::consts::PI);
+    ctxt.set_source_rgba(1., 1., 1., 0.8);
+    ctxt.set_line_width(data.draw_info.get_actual_width(1.5));
+    ctxt.stroke();
+
+    if !data.show_orbit {
+        return;
+    }
+    let primary_index = match data.orbit_primary(selected) {
+        Some(index) => index,
+        None => return,
+    };
+    let primary = &objects[primary_index];
+    let mut lines = vec![format!("orbit around {}", data.universe.label(primary_index))];
+    match OrbitalElements::from_objects(object, primary) {
+        Some(elements) => {
+            draw_orbit(ctxt, &data.draw_info, primary, &elements);
+            lines.extend(elements.describe());
+        }
+        None => lines.push("undefined".to_string()),
+    }
+    // readout in screen space next to the selected object
+    let (sx, sy) = data.draw_info.get_screen_point(object.position().x, object.position().y);
+    let offset = object.radius() / data.draw_info.get_actual_width(1.) + 10.;
+    ctxt.save();
+    ctxt.identity_matrix();
+    draw_text_lines(ctxt, sx + offset, sy + offset, &lines, 1., 1., 0.4);
+    ctxt.restore();
 }
@@ -0,0 +1,164 @@
+use cairo::Context;
+use draw::{draw_text_lines, TEXT_LINE_HEIGHT};
+use trails::TrailLimit;
+use draw::Anchor;
+use integrator::Integrator;
+use forces::MIN_PARALLEL_OBJECTS;
+use super::data::UiData;
+use super::state::*;
+
+const HUD_MARGIN: f64 = 10.;
+
+// status text in the top left corner
+// expects the identity matrix to be set
+pub fn draw_hud(data: &UiData, ctxt: &Context) {
+    let state = match data.state {
+        UiState::Normal => "running",
+        UiState::Paused => "paused",
+        UiState::Edit(_) => "edit",
+    };
+    let mut lines = vec![format!("state: {}", state),
+                         format!("simulated time: {:.0}", data.universe.elapsed()),
+                         step_line(data),
+                         integrator_line(data),
+                         threads_line(data),
+                         format!("objects: {}", data.universe.objects().len()),
+                         view_line(data),
+                         trail_line(data),
+                         format!("fps: {:.1}", data.frame_rate.rate()),
+                         format!("updater steps/s: {:.1}", data.step_rate.rate()),
+                         format!("speed: x{} ({}, actual {:.0})",
+                                 data.speed,
+                                 data.sim_rate().describe(),
+                                 data.sim_time_rate.rate()),
+                         format!("repeated frames: {}, skipped steps: {}",
+                                 data.repeated_frames,
+                                 data.skipped_steps)];
+    if let Some(line) = bookmark_line(data) {
+        lines.push(line);
+    }
+    if let UiState::Edit(EditState::Mouse(ref mouse_edit_state)) = data.state {
+        lines.push(mouse_edit_state.hint().to_string());
+    }
+    if let Some(status) = data.status() {
+        lines.push(status.to_string());
+    }
+    ctxt.rectangle(HUD_MARGIN / 2.,
+                   HUD_MARGIN / 2.,
+                   440.,
+                   lines.len() as f64 * TEXT_LINE_HEIGHT + HUD_MARGIN);
+    ctxt.set_source_rgba(0., 0., 0., 0.6);
+    ctxt.fill();
+    draw_text_lines(ctxt, HUD_MARGIN, HUD_MARGIN, &lines, 1., 1., 1.);
+}
+
+// the saved views, or the name of the one being named
+fn bookmark_line(data: &UiData) -> Option<String> {
+    if let Some(NameEntry { target: NameTarget::Bookmark(ref slot), ref text }) =
+           data.name_entry {
+        return Some(format!("name view {}: {}_", slot + 1, text));
+    }
+    let names = data.bookmarks.names();
+    if names.is_empty() {
+        return None;
+    }
+    let names: Vec<String> = names.iter()
+        .map(|&(slot, name)| format!("{} {}", slot + 1, name))
+        .collect();
+    Some(format!("views: {}", names.join(", ")))
+}
+
+fn view_line(data: &UiData) -> String {
+    let view = data.draw_info.view();
+    format!("view: rotated {:.0}°, aspect {}{}",
+            (view.rotation.to_degrees() % 360. + 360.) % 360.,
+            if data.draw_info.aspect_locked() {
+                "locked"
+            } else {
+                "free"
+            },
+            if data.draw_info.auto_fit() {
+                ", auto-fit"
+            } else {
+                ""
+            })
+}
+
+fn trail_line(data: &UiData) -> String {
+    let settings = data.universe.trail_settings();
+    let length = match settings.limit {
+        TrailLimit::Points(points) => format!("{} points", points),
+        TrailLimit::Time(time) => format!("{:.0} time", time),
+    };
+    let frame = match data.draw_info.path_frame() {
+        Some(&Anchor::Barycenter) => ", around barycenter".to_string(),
+        Some(&Anchor::Object(id)) => {
+            match data.universe.index_of(id) {
+                Some(index) => format!(", around {}", data.universe.label(index)),
+                None => String::new(),
+            }
+        }
+        None => String::new(),
+    };
+    format!("trails: {}, sampled every {} steps{}{}",
+            length,
+            settings.sample_interval,
+            if data.draw_info.fade_paths() { ", faded" } else { "" },
+            frame)
+}
+
+fn step_line(data: &UiData) -> String {
+    let settings = &data.update_settings;
+    match settings.adaptive() {
+        Some(adaptive) => {
+            format!("step time: {}, adaptive dt {:.2} ({:.2} - {:.2})",
+                    settings.time(),
+                    data.universe.last_dt(),
+                    adaptive.min_dt(settings.fixed_dt()),
+                    adaptive.max_dt(settings.fixed_dt()))
+        }
+        None => {
+            format!("step time: {} x {} iterations, dt {:.2}",
+                    settings.time(),
+                    settings.iterations(),
+                    data.universe.last_dt())
+        }
+    }
+}
+
+// only the force calculation of our own integrators is spread over threads, stepping
+// and collisions stay on the updater thread
+fn threads_line(data: &UiData) -> String {
+    let settings = &data.update_settings;
+    if let Integrator::Library = settings.integrator() {
+        return "force threads: unused, the library integrator has its own forces".to_string();
+    }
+    format!("force threads: {} (forces only), {}{}",
+            settings.threads(),
+            if settings.deterministic() {
+                "deterministic"
+            } else {
+                "nondeterministic"
+            },
+            if settings.threads() > 1 && data.universe.objects().len() < MIN_PARALLEL_OBJECTS {
+                format!(", single threaded below {} objects", MIN_PARALLEL_OBJECTS)
+            } else {
+                String::new()
+            })
+}
+
+fn integrator_line(data: &UiData) -> String {
+    let settings = &data.update_settings;
+    let name = match settings.integrator() {
+        Integrator::Library => format!("{} (own forces)", settings.integrator().name()),
+        integrator => format!("{}, {} forces", integrator.name(), settings.forces().describe()),
+    };
+    match (data.reference_energy, data.energy) {
+        (Some(reference), Some((energy, _))) if reference != 0. => {
+            format!("integrator: {}, energy drift: {:.3e}",
+                    name,
+                    (energy - reference) / reference.abs())
+        }
+        _ => format!("integrator: {}", name),
+    }
+}
@@ -2,14 +2,31 @@ use sharedstate::SharedState;
 use gdk::EventKey;
 use gdk::enums::key;
 use updater::UpdaterCommand;
-use std::sync::mpsc::TryRecvError;
 use coloruniverse::ColorUniverse;
+use trails::{TrailLimit, MAX_POSITIONS_STORED};
+use barneshut::{ForceMode, DEFAULT_THETA, MAX_THETA};
+use draw::Anchor;
 
 use super::data::UiData;
 use super::state::*;
 
+// radians turned by each press of [ or ]
+const ROTATION_STEP: f64 = ::std::f64::consts::PI / 12.;
+// how much + and - change the length of the vector overlays
+const VECTOR_SCALE_STEP: f64 = 1.25;
+// how much , and . change the length of trails
+const TRAIL_LENGTH_STEP: f64 = 2.;
+const SAMPLE_INTERVALS: [usize; 4] = [1, 2, 4, 8];
+const THETA_STEP: f64 = 0.1;
+
 pub fn key_press_handler(data: &SharedState<UiData>, key: &EventKey) {
     let ref mut data = *data.get_state_mut();
+    // while naming an object every key goes to the name
+    if data.name_entry.is_some() {
+        data.name_entry_keys.push(key.get_keyval());
+        name_entry_key_press(data, key.get_keyval());
+        return;
+    }
     match key.get_keyval() {
         key::Shift_L | key::Shift_R => {
             data.input_info.shift = true;
@@ -29,14 +46,66 @@ pub fn key_press_handler(data: &SharedState<UiData>, key: &EventKey) {
         key::Right => {
             data.input_info.right = true;
         }
+        // number keys recall saved views, ctrl stores the current one and names it
+        // on press, since ctrl may already be up by the time the number is released
+        k if k >= key::_1 && k <= key::_9 => {
+            let slot = (k - key::_1) as usize;
+            if data.input_info.ctrl {
+                data.store_bookmark(slot);
+            } else {
+                data.recall_bookmark(slot);
+            }
+        }
         _ => {
             println!("keypressed");
         }
     }
 }
 
+fn name_entry_key_press(data: &mut UiData, keyval: u32) {
+    match keyval {
+        key::Return | key::KP_Enter => {
+            data.finish_name_entry();
+        }
+        key::Escape => {
+            data.name_entry = None;
+        }
+        key::BackSpace => {
+            if let Some(ref mut entry) = data.name_entry {
+                entry.text.pop();
+            }
+        }
+        _ => {
+            if let (Some(entry), Some(c)) = (data.name_entry.as_mut(),
+                                              ::gdk::keyval_to_unicode(keyval)) {
+                if !c.is_control() {
+                    entry.text.push(c);
+                }
+            }
+        }
+    }
+}
+
 pub fn key_release_handler(data: &SharedState<UiData>, key: &EventKey) {
     let ref mut data = *data.get_state_mut();
+    // modifiers still need to be let go of while naming an object, and keys that went
+    // to the name, like the return or escape that finished it, are done with
+    let keyval = key.get_keyval();
+    let consumed = match data.name_entry_keys.iter().position(|&k| k == keyval) {
+        Some(position) => {
+            data.name_entry_keys.remove(position);
+            true
+        }
+        None => false,
+    };
+    if data.name_entry.is_some() || consumed {
+        match key.get_keyval() {
+            key::Shift_L | key::Shift_R => data.input_info.shift = false,
+            key::Control_L | key::Control_R => data.input_info.ctrl = false,
+            _ => {}
+        }
+        return;
+    }
     match key.get_keyval() {
         key::G | key::g => {
             data.draw_info.toggle_grid();
@@ -53,6 +122,9 @@ pub fn key_release_handler(data: &SharedState<UiData>, key: &EventKey) {
                     data.update_command_send
                         .send(UpdaterCommand::Pause)
                         .unwrap();
+                    // the updater carries on from what's on screen, not from what it
+                    // had already computed
+                    data.push_universe();
                     UiState::Paused
                 }
             };
@@ -70,6 +142,7 @@ pub fn key_release_handler(data: &SharedState<UiData>, key: &EventKey) {
                     data.update_command_send
                         .send(UpdaterCommand::Pause)
                         .unwrap();
+                    data.push_universe();
                     UiState::Edit(EditState::default())
                 }
             };
@@ -77,17 +150,76 @@ pub fn key_release_handler(data: &SharedState<UiData>, key: &EventKey) {
         }
         key::R | key::r => {
             data.universe = ColorUniverse::default();
-            data.update_command_send
-                .send(UpdaterCommand::SetUniverse(data.universe.clone()))
-                .unwrap();
-            // clear receiver
-            let mut clear = false;
-            while !clear {
-                match data.universe_recv.try_recv() {
-                    Ok(_) => {}
-                    Err(TryRecvError::Empty) => clear = true,
-                    Err(e) => println!("error: {:?}", e),
-                }
+            data.push_universe();
+        }
+        key::Z | key::z => {
+            data.universe.zero_momentum();
+            data.push_universe();
+        }
+        // the reference frame, what the view follows and what paths are drawn relative
+        // to each cycle through the selected object and the barycenter
+        key::B | key::b => {
+            let next = next_anchor(data.draw_info.frame(), data.selected);
+            data.draw_info.set_frame(&data.universe, next);
+        }
+        key::T | key::t => {
+            let next = next_anchor(data.draw_info.follow(), data.selected);
+            data.draw_info.set_follow(&data.universe, next);
+        }
+        key::U | key::u => {
+            let next = next_anchor(data.draw_info.trail_frame(), data.selected);
+            data.draw_info.set_trail_frame(next);
+        }
+        // toggle running as fast as possible instead of at the target rate
+        key::W | key::w => {
+            data.toggle_fast_as_possible();
+        }
+        // toggle choosing the number of iterations from how close objects get
+        key::Y | key::y => {
+            data.update_settings.toggle_adaptive();
+            data.send_update_settings();
+        }
+        key::Tab => {
+            data.next_integrator();
+        }
+        // barnes-hut forces, the tree overlay and its opening angle
+        key::F5 => {
+            let forces = match data.update_settings.forces() {
+                ForceMode::Direct => ForceMode::BarnesHut { theta: DEFAULT_THETA },
+                ForceMode::BarnesHut { .. } => ForceMode::Direct,
+            };
+            data.set_forces(forces);
+        }
+        key::F6 => {
+            data.show_tree = !data.show_tree;
+        }
+        key::F7 | key::F8 => {
+            if let ForceMode::BarnesHut { theta } = data.update_settings.forces() {
+                let theta = if key.get_keyval() == key::F7 {
+                    theta - THETA_STEP
+                } else {
+                    theta + THETA_STEP
+                };
+                data.set_forces(ForceMode::BarnesHut { theta: theta.max(0.).min(MAX_THETA) });
+            }
+        }
+        // threads the forces are worked out on, and whether that changes the results
+        // only forces, stepping and collisions always run on the updater thread
+        key::F9 | key::F10 => {
+            let more = key.get_keyval() == key::F10;
+            data.step_threads(more);
+        }
+        key::F12 => {
+            data.toggle_deterministic();
+        }
+        // speed up, shift slows down and ctrl reverses
+        key::S | key::s => {
+            if data.input_info.ctrl {
+                let speed = -data.speed;
+                data.set_speed(speed);
+            } else {
+                let up = !data.input_info.shift;
+                data.step_speed(up);
             }
         }
         key::Shift_L | key::Shift_R => {
@@ -122,8 +254,120 @@ pub fn key_release_handler(data: &SharedState<UiData>, key: &EventKey) {
         key::D | key::d => {
             data.draw_info.toggle_paths();
         }
+        // fit all, shift fits the selection, ctrl toggles continuous fitting
+        key::F | key::f => {
+            if data.input_info.ctrl {
+                data.draw_info.toggle_auto_fit();
+            } else {
+                let selection_only = data.input_info.shift;
+                data.fit_view(selection_only);
+            }
+        }
+        key::O | key::o => {
+            data.show_orbit = !data.show_orbit;
+        }
+        key::Escape => {
+            data.selected = None;
+        }
+        // name the selected object
+        key::N | key::n => {
+            data.start_naming_selected();
+        }
+        key::V | key::v => {
+            let settings = data.draw_info.vector_settings_mut();
+            settings.velocity = !settings.velocity;
+        }
+        key::C | key::c => {
+            let settings = data.draw_info.vector_settings_mut();
+            settings.acceleration = !settings.acceleration;
+        }
+        key::L | key::l => {
+            let settings = data.draw_info.vector_settings_mut();
+            settings.log_scale = !settings.log_scale;
+        }
+        key::plus | key::equal | key::KP_Add => {
+            data.draw_info.vector_settings_mut().multiply_scale(VECTOR_SCALE_STEP);
+        }
+        key::minus | key::KP_Subtract => {
+            data.draw_info.vector_settings_mut().multiply_scale(1. / VECTOR_SCALE_STEP);
+        }
+        // trail length, < and > only change the selected object
+        key::comma => {
+            data.scale_trails(1. / TRAIL_LENGTH_STEP);
+        }
+        key::period => {
+            data.scale_trails(TRAIL_LENGTH_STEP);
+        }
+        key::less => {
+            data.scale_selected_trail(1. / TRAIL_LENGTH_STEP);
+        }
+        key::greater => {
+            data.scale_selected_trail(TRAIL_LENGTH_STEP);
+        }
+        // switch between limiting trails by point count and by simulated time
+        key::apostrophe => {
+            let mut settings = data.universe.trail_settings();
+            let sample_time = data.update_settings.time() * settings.sample_interval as f64;
+            settings.limit = match settings.limit {
+                TrailLimit::Points(points) => TrailLimit::Time(points as f64 * sample_time),
+                TrailLimit::Time(time) => {
+                    let points = (time / sample_time) as usize;
+                    TrailLimit::Points(points.max(1).min(MAX_POSITIONS_STORED))
+                }
+            };
+            data.universe.set_trail_settings(settings);
+        }
+        key::slash => {
+            let mut settings = data.universe.trail_settings();
+            let position = SAMPLE_INTERVALS.iter()
+                .position(|&i| i == settings.sample_interval)
+                .unwrap_or(0);
+            settings.sample_interval = SAMPLE_INTERVALS[(position + 1) % SAMPLE_INTERVALS.len()];
+            data.universe.set_trail_settings(settings);
+        }
+        key::semicolon => {
+            data.draw_info.toggle_fade_paths();
+        }
+        key::Q | key::q => {
+            data.heatmap.cycle();
+        }
+        key::X | key::x => {
+            data.field_overlay.cycle();
+        }
+        key::J | key::j => {
+            data.draw_info.next_palette();
+        }
+        key::K | key::k => {
+            data.draw_info.next_color_mode();
+        }
+        key::H | key::h => {
+            data.show_hud = !data.show_hud;
+        }
+        key::I | key::i => {
+            data.show_minimap = !data.show_minimap;
+        }
+        key::A | key::a => {
+            data.draw_info.toggle_aspect_lock();
+        }
+        key::bracketleft => {
+            data.draw_info.rotate_smooth(-ROTATION_STEP);
+        }
+        key::bracketright => {
+            data.draw_info.rotate_smooth(ROTATION_STEP);
+        }
         _ => {
             println!("keypress");
         }
     }
 }
+
+// none -> selected object -> barycenter -> none, skipping the selected object when
+// nothing is selected
+fn next_anchor(current: Option<&Anchor>, selected: Option<usize>) -> Option<Anchor> {
+    match (current, selected) {
+        (None, Some(id)) => Some(Anchor::Object(id)),
+        (None, None) |
+        (Some(&Anchor::Object(_)), _) => Some(Anchor::Barycenter),
+        (Some(&Anchor::Barycenter), _) => None,
+    }
+}
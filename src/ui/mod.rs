@@ -1,8 +1,8 @@
+use std::path::Path;
 use gtk::prelude::*;
 use gtk::{self, Window, WindowType, DrawingArea, Orientation};
 use sharedstate::SharedState;
-use std::sync::mpsc::TryRecvError;
-use updater::{UpdaterCommand, Updater};
+use updater::{Updater, UpdateSettings};
 use iteration_result::IterationResult;
 use coloruniverse::ColorUniverse;
 use input::MOUSE_MOVEMENT_BORDER_WIDTH;
@@ -12,8 +12,9 @@ mod data;
 mod draw_handler;
 mod key_handler;
 mod mouse_handler;
+mod hud;
 
-use self::data::UiData;
+use self::data::{UiData, SPEEDS};
 use self::draw_handler::*;
 use self::key_handler::*;
 use self::mouse_handler::*;
@@ -25,9 +26,9 @@ pub struct Ui {
 }
 
 impl Ui {
-    pub fn initialize() -> Ui {
-        let (mut updater, universe_recv, update_command_send) =
-            Updater::new(ColorUniverse::default());
+    pub fn initialize(update_settings: UpdateSettings, scenario: Option<&Path>) -> Ui {
+        let (mut updater, latest_state, update_command_send) =
+            Updater::new(ColorUniverse::default(), update_settings.clone());
 
         let window = default_window();
         let mainsplit = gtk::Box::new(Orientation::Vertical, 10);
@@ -35,14 +36,16 @@ impl Ui {
         let input_interface = gtk::Box::new(Orientation::Vertical, 10);
         let time_scale = gtk::Scale::new_with_range(Orientation::Horizontal, 1., 100_000., 1000.);
         let accuracy_scale = gtk::Scale::new_with_range(Orientation::Horizontal, 1., 100_000., 1000.);
+        let speed_controls = gtk::Box::new(Orientation::Horizontal, 5);
         input_interface.add(&time_scale);
         input_interface.add(&accuracy_scale);
+        input_interface.add(&speed_controls);
         mainsplit.pack_start(&draw_area, true, true, 0);
         mainsplit.pack_end(&input_interface, false, true, 0);
         window.add(&mainsplit);
         window.show_all();
 
-        let data = UiData::new(universe_recv, update_command_send);
+        let data = UiData::new(latest_state, update_command_send, update_settings, scenario);
 
         let this = Ui {
             data: SharedState::new(data),
@@ -53,6 +56,7 @@ impl Ui {
         this.setup_mouse_callbacks();
         this.setup_key_callbacks(&window);
         this.setup_window_callbacks(&window);
+        this.setup_speed_controls(&speed_controls);
 
         ::std::thread::spawn(move || {
             loop {
@@ -137,6 +141,30 @@ impl Ui {
         }
     }
 
+    // a button for every speed multiplier and one to run backwards
+    fn setup_speed_controls(&self, speed_controls: &gtk::Box) {
+        for &speed in SPEEDS.iter() {
+            let button = gtk::Button::new_with_label(&format!("x{}", speed));
+            let data = self.data.clone();
+            button.connect_clicked(move |_| {
+                let ref mut data = *data.get_state_mut();
+                let speed = speed * data.speed.signum();
+                data.set_speed(speed);
+            });
+            speed_controls.add(&button);
+        }
+
+        let reverse = gtk::Button::new_with_label("reverse");
+        let data = self.data.clone();
+        reverse.connect_clicked(move |_| {
+            let ref mut data = *data.get_state_mut();
+            let speed = -data.speed;
+            data.set_speed(speed);
+        });
+        speed_controls.add(&reverse);
+        speed_controls.show_all();
+    }
+
     fn setup_window_callbacks(&self, window: &Window) {
         window.connect_delete_event(|_, _| {
             gtk::main_quit();
@@ -198,44 +226,28 @@ impl Ui {
         self.handle_input_iteration();
 
         let ref mut data = *self.data.get_state_mut();
-        if data.fps_info.should_redraw() {
-            self.draw_area.queue_draw();
+
+        // check the updater output, while paused the ui's universe is the current one
+        if let UiState::Normal = data.state {
+            data.receive_universe();
         }
 
-        // check the updater output
-        match data.state {
-            UiState::Paused | UiState::Edit(_) => {
-                // set the current universe
-                data.update_command_send
-                    .send(UpdaterCommand::SetUniverse(data.universe.clone()))
-                    .unwrap();
-                // clear the receiver
-                let mut clear = false;
-                while !clear {
-                    match data.universe_recv.try_recv() {
-                        Ok(_) => {}
-                        Err(TryRecvError::Empty) => clear = true,
-                        Err(e) => {
-                            // should never happen
-                            return IterationResult::Error(format!("{}", e));
-                        }
-                    }
-                }
-            }
-            _ => {
-                match data.universe_recv.try_recv() {
-                    Ok(new_universe) => {
-                        data.universe = new_universe;
-                        // tell the updater it has consumed a state
-                        data.update_command_send.send(UpdaterCommand::UniverseConsumed).unwrap();
-                    }
-                    Err(TryRecvError::Empty) => {}
-                    Err(e) => {
-                        // should never happen
-                        return IterationResult::Error(format!("{}", e));
-                    }
-                }
-            }
+        data.update_energy();
+
+        let steps = data.universe.steps() as f64;
+        data.step_rate.update(steps);
+        let clock = data.universe.clock();
+        data.sim_time_rate.update(clock);
+
+        // keep the followed object in place using the newest universe before drawing
+        data.draw_info.resolve_anchors(&data.universe);
+        data.update_bounds();
+        if data.draw_info.auto_fit() {
+            data.fit_view(false);
+        }
+        data.draw_info.step_transition();
+        if data.fps_info.should_redraw() {
+            self.draw_area.queue_draw();
         }
 
         IterationResult::Ok
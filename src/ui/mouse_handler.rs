@@ -1,17 +1,34 @@
 use sharedstate::SharedState;
 use gdk::{EventButton, EventScroll, EventMotion};
-use updater::UpdaterCommand;
 use fpsinfo::DEFAULT_FPS;
-use color::ObjectColor;
 
 use super::data::UiData;
 use super::state::*;
 use physics_sim::*;
 
-pub fn mouse_press_handler(data: &SharedState<UiData>, button: &EventButton) {}
+// how far outside of an object (in pixels) a click still selects it
+const SELECT_TOLERANCE: f64 = 8.;
+// zoom factor for each scroll event
+const ZOOM_STEP: f64 = 1.01;
+
+pub fn mouse_press_handler(data: &SharedState<UiData>, button: &EventButton) {
+    let ref mut data = *data.get_state_mut();
+    if button.get_button() == 1 {
+        let (x, y) = button.get_position();
+        if data.minimap().map_or(false, |minimap| minimap.contains(x, y)) {
+            data.minimap_drag = true;
+            data.recenter_from_minimap();
+        }
+    }
+}
 
 pub fn mouse_release_handler(data: &SharedState<UiData>, button: &EventButton) {
     let ref mut data = *data.get_state_mut();
+    // clicks on the minimap don't go through to the view
+    if data.minimap_drag && button.get_button() == 1 {
+        data.minimap_drag = false;
+        return;
+    }
     match button.get_button() {
         // left click
         1 => {
@@ -36,10 +53,9 @@ pub fn mouse_release_handler(data: &SharedState<UiData>, button: &EventButton) {
                         let new_object =
                             Object::new(mass, Vector::new(v_magnitude, line_angle), point);
 
-                        data.universe.add_object(new_object, ObjectColor::FromMass);
-                        data.update_command_send
-                            .send(UpdaterCommand::SetUniverse(data.universe.clone()))
-                            .unwrap();
+                        let color = data.draw_info.color_mode().clone();
+                        data.universe.add_object(new_object, color);
+                        data.push_universe();
                         // go back to initial state
                         MouseEditState::SetPoint
                     }
@@ -50,17 +66,28 @@ pub fn mouse_release_handler(data: &SharedState<UiData>, button: &EventButton) {
             if let UiState::Edit(EditState::Mouse(ref mut mouse_edit_state)) = data.state {
                 if let MouseEditState::SetVelocity(mass, point) = *mouse_edit_state {
                     let new_object = Object::new(mass, Vector::default(), point);
-                    data.universe.add_object(new_object, ObjectColor::FromMass);
-                    data.update_command_send
-                        .send(UpdaterCommand::SetUniverse(data.universe.clone()))
-                        .unwrap();
+                    let color = data.draw_info.color_mode().clone();
+                    data.universe.add_object(new_object, color);
+                    data.push_universe();
                     // go back to initial state
                     *mouse_edit_state = MouseEditState::SetPoint
                 }
             }
         }
+        // right click selects, shift right click picks the orbit primary
         3 => {
-
+            let mouse_raw = data.draw_info
+                .get_actual_point(data.input_info.mouse_x, data.input_info.mouse_y);
+            let mouse = Point::new(mouse_raw.0, mouse_raw.1);
+            let tolerance = data.draw_info.get_actual_width(SELECT_TOLERANCE);
+            let clicked = data.universe
+                .object_at(&mouse, tolerance)
+                .and_then(|index| data.universe.id_of(index));
+            if data.input_info.shift {
+                data.orbit_primary = clicked;
+            } else {
+                data.selected = clicked;
+            }
         }
         _ => {
             if let UiState::Edit(EditState::Mouse(ref mut mouse_edit_state)) = data.state {
@@ -77,20 +104,20 @@ pub fn mouse_scroll_handler(data: &SharedState<UiData>, scroll: &EventScroll) {
         ::gdk_sys::GdkScrollDirection::Up => {
             if !(data.input_info.ctrl ^ data.input_info.shift) {
                 // either or none
-                data.draw_info.scale(x, y, 1.01, 1.01);
+                data.draw_info.scale_smooth(x, y, ZOOM_STEP, ZOOM_STEP);
             } else if data.input_info.ctrl {
-                data.draw_info.scale(x, y, 1.01, 1.);
+                data.draw_info.scale_smooth(x, y, ZOOM_STEP, 1.);
             } else if data.input_info.shift {
-                data.draw_info.scale(x, y, 1., 1.01);
+                data.draw_info.scale_smooth(x, y, 1., ZOOM_STEP);
             }
         }
         ::gdk_sys::GdkScrollDirection::Down => {
             if !(data.input_info.ctrl ^ data.input_info.shift) {
-                data.draw_info.scale(x, y, 0.99, 0.99);
+                data.draw_info.scale_smooth(x, y, 1. / ZOOM_STEP, 1. / ZOOM_STEP);
             } else if data.input_info.ctrl {
-                data.draw_info.scale(x, y, 0.99, 1.);
+                data.draw_info.scale_smooth(x, y, 1. / ZOOM_STEP, 1.);
             } else if data.input_info.shift {
-                data.draw_info.scale(x, y, 1., 0.99);
+                data.draw_info.scale_smooth(x, y, 1., 1. / ZOOM_STEP);
             }
         }
         _ => {}
@@ -102,4 +129,7 @@ pub fn mouse_motion_handler(data: &SharedState<UiData>, motion: &EventMotion) {
     let (mx, my) = motion.get_position();
     data.input_info.mouse_x = mx;
     data.input_info.mouse_y = my;
+    if data.minimap_drag {
+        data.recenter_from_minimap();
+    }
 }
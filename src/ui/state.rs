@@ -34,3 +34,28 @@ impl Default for MouseEditState {
         MouseEditState::SetPoint
     }
 }
+
+impl MouseEditState {
+    pub fn hint(&self) -> &'static str {
+        match *self {
+            MouseEditState::SetPoint => "click to place a new object",
+            MouseEditState::SetMass(_) => "click to set the size and mass",
+            MouseEditState::SetVelocity(..) => {
+                "click to set the velocity, middle click for a stationary object"
+            }
+        }
+    }
+}
+
+// what a name being typed is for
+pub enum NameTarget {
+    // by id
+    Object(usize),
+    // a view bookmark slot
+    Bookmark(usize),
+}
+
+pub struct NameEntry {
+    pub target: NameTarget,
+    pub text: String,
+}
@@ -1,98 +1,227 @@
 use std::sync::mpsc::{channel, Sender, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use coloruniverse::ColorUniverse;
 use iteration_result::IterationResult;
+use fpsinfo::DEFAULT_FPS;
+use integrator::Integrator;
+use barneshut::ForceMode;
+use forces::Forces;
 
-pub const UNIVERSE_CACHE_LIMIT: usize = 10;
+// real time spent stepping in one iteration at most, so the ui gets states regularly
+const MAX_STEPPING_TIME: f64 = 1. / DEFAULT_FPS;
+// real time worth of simulated time that can be owed, any more is dropped so a slow
+// simulation doesn't spiral trying to catch up
+const MAX_TIME_OWED: f64 = 0.25;
+// longest sleep between iterations, so commands are still picked up quickly
+const MAX_SLEEP_TIME: f64 = 0.05;
+pub const MAX_THREADS: usize = 64;
+
+// how fast simulated time passes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SimRate {
+    // simulated seconds per real second, negative runs backwards
+    Target(f64),
+    // step as often as possible, ignoring real time
+    FastAsPossible { reverse: bool },
+}
+
+impl SimRate {
+    pub fn describe(&self) -> String {
+        match *self {
+            SimRate::Target(rate) => format!("{:.0} per second", rate),
+            SimRate::FastAsPossible { reverse: false } => "as fast as possible".to_string(),
+            SimRate::FastAsPossible { reverse: true } => {
+                "as fast as possible, backwards".to_string()
+            }
+        }
+    }
+}
+
+// newest state from the updater, older ones are overwritten if the ui hasn't taken them
+// states are tagged with the generation of the universe they came from, so ones made
+// before the ui replaced the universe can be told apart
+#[derive(Clone)]
+pub struct LatestState {
+    inner: Arc<Mutex<Option<(usize, ColorUniverse)>>>,
+}
+
+impl LatestState {
+    fn new() -> LatestState {
+        LatestState { inner: Arc::new(Mutex::new(None)) }
+    }
+
+    fn put(&self, generation: usize, universe: ColorUniverse) {
+        *self.inner.lock().unwrap() = Some((generation, universe));
+    }
+
+    // the newest state, if there is one that belongs to the generation
+    pub fn take(&self, generation: usize) -> Option<ColorUniverse> {
+        match self.inner.lock().unwrap().take() {
+            Some((g, universe)) if g == generation => Some(universe),
+            _ => None,
+        }
+    }
+
+    // nobody is left to take states
+    fn abandoned(&self) -> bool {
+        Arc::strong_count(&self.inner) == 1
+    }
+}
 
 pub struct Updater {
-    update_send: Sender<ColorUniverse>,
+    latest_state: LatestState,
     update_command_recv: Receiver<UpdaterCommand>,
     update_settings: UpdateSettings,
+    // kept alongside the settings so its threads aren't started again every step
+    forces: Forces,
     universe: ColorUniverse,
+    generation: usize,
     paused: bool,
-    fps_update_time: f64,
-    consumption_count: isize,
-    cache_fill: usize,
+    rate: SimRate,
+    last_time: f64,
+    // simulated time that should have been stepped through but hasn't yet
+    time_owed: f64,
 }
 
 impl Updater {
-    pub fn new(universe: ColorUniverse) -> (Updater, Receiver<ColorUniverse>, Sender<UpdaterCommand>) {
+    pub fn new(universe: ColorUniverse,
+               update_settings: UpdateSettings)
+               -> (Updater, LatestState, Sender<UpdaterCommand>) {
         //let mut universe = universe.clone();
         //universe.add_object(Object::new(200000., Vector::default(), Point::new(0., 0.)), ObjectColor::FromMass);
         //universe.add_object(Object::new(10000., Vector::new(120., 0.), Point::new(0., 5000.)), ObjectColor::FromMass);
         //universe.add_object(Object::new(1., Vector::new(53., 0.), Point::new(0., 140.)), ObjectColor::FromMass);
 
-        let (update_send, update_recv) = channel();
+        let latest_state = LatestState::new();
         let (update_command_send, update_command_recv) = channel();
         (Updater {
-            update_send: update_send,
+            latest_state: latest_state.clone(),
             update_command_recv: update_command_recv,
-            update_settings: UpdateSettings::default(),
+            rate: SimRate::Target(update_settings.default_rate()),
+            forces: Forces::new(update_settings.forces,
+                                update_settings.threads,
+                                update_settings.deterministic),
+            update_settings: update_settings,
             universe: universe,
+            generation: 0,
             paused: false,
-            fps_update_time: 1./60.,
-            consumption_count: 0,
-            cache_fill: 0,
+            last_time: ::time::precise_time_s(),
+            time_owed: 0.,
         },
-        update_recv,
+        latest_state,
         update_command_send)
     }
 
-    // WIP/TODO
     pub fn iterate(&mut self) -> IterationResult {
-        let start_time = ::time::precise_time_s();
         // check for any new settings
-        match self.update_command_recv.try_recv() {
-            Ok(command) => {
-                match command {
-                    UpdaterCommand::UpdateSettings(new_settings) => {
-                        self.update_settings = new_settings;
-                    }
-                    UpdaterCommand::Pause => {
-                        self.paused = true;
-                    }
-                    UpdaterCommand::Unpause => {
-                        self.paused = false;
-                        self.consumption_count = 0;
-                        self.cache_fill = 0;
-                    }
-                    UpdaterCommand::SetFpsUpdateTime(update_time) => {
-                        self.fps_update_time = update_time;
-                    }
-                    UpdaterCommand::SetUniverse(universe) => {
-                        self.universe = universe;
-                    }
-                    UpdaterCommand::UniverseConsumed => {
-                        self.consumption_count += 1;
-                    }
+        loop {
+            match self.update_command_recv.try_recv() {
+                Ok(command) => self.handle_command(command),
+                Err(TryRecvError::Empty) => break,
+                Err(e) => return IterationResult::Error(format!("{}", e)),
+            }
+        }
+        if self.latest_state.abandoned() {
+            return IterationResult::Finished;
+        }
+
+        let now = ::time::precise_time_s();
+        let real_elapsed = now - self.last_time;
+        self.last_time = now;
+        if self.paused {
+            sleep(MAX_SLEEP_TIME);
+            return IterationResult::Ok;
+        }
+
+        // the step size stays the same whatever the rate, only the number of steps changes
+        let step_time = self.update_settings.time;
+        match self.rate {
+            SimRate::Target(rate) if rate != 0. => {
+                let speed = rate.abs();
+                self.time_owed = (self.time_owed + real_elapsed * speed)
+                    .min((speed * MAX_TIME_OWED).max(step_time));
+                let mut stepped = false;
+                while self.time_owed >= step_time &&
+                      ::time::precise_time_s() - now < MAX_STEPPING_TIME {
+                    self.step(rate < 0.);
+                    self.time_owed -= step_time;
+                    stepped = true;
                 }
-            },
-            Err(TryRecvError::Empty) => {},
-            Err(e) => return IterationResult::Error(format!("{}", e)),
+                if stepped {
+                    self.publish();
+                }
+                // wait until the next step is due
+                sleep(((step_time - self.time_owed) / speed).min(MAX_SLEEP_TIME));
+            }
+            SimRate::Target(_) => sleep(MAX_SLEEP_TIME),
+            SimRate::FastAsPossible { reverse } => {
+                self.time_owed = 0.;
+                self.step(reverse);
+                self.publish();
+            }
         }
-        // update the universe
-        if !self.paused && (self.cache_fill < UNIVERSE_CACHE_LIMIT || self.consumption_count > 0) {
-            self.universe
-                .update_state_repeat(self.update_settings.time, self.update_settings.iterations);
-
-            // write out the new universe to the ringbuffer
-            if let Err(_) = self.update_send.send(self.universe.clone()) {
-                return IterationResult::Finished;
+        // continue
+        IterationResult::Ok
+    }
+
+    fn handle_command(&mut self, command: UpdaterCommand) {
+        match command {
+            UpdaterCommand::UpdateSettings(new_settings) => {
+                self.forces.mode = new_settings.forces;
+                self.forces.deterministic = new_settings.deterministic;
+                self.forces.set_threads(new_settings.threads);
+                self.update_settings = new_settings;
+            }
+            UpdaterCommand::Pause => {
+                self.paused = true;
             }
-            if self.cache_fill < UNIVERSE_CACHE_LIMIT {
-                self.cache_fill += 1;
-            } else {
-                self.consumption_count -= 1;
+            UpdaterCommand::Unpause => {
+                self.paused = false;
+                self.time_owed = 0.;
+            }
+            UpdaterCommand::SetRate(rate) => {
+                self.rate = rate;
+                self.time_owed = 0.;
+            }
+            UpdaterCommand::SetUniverse(universe, generation) => {
+                // it may be older than the states that were already sampled
+                universe.rewind_trails();
+                self.universe = universe;
+                self.generation = generation;
             }
         }
+    }
 
-        let time_taken = ::time::precise_time_s() - start_time;
-        let time_sleep = self.fps_update_time - time_taken;
-        if time_sleep > 0. {
-            ::std::thread::sleep(::std::time::Duration::from_millis((time_sleep * 1000.) as u64));
+    fn step(&mut self, reverse: bool) {
+        let time = if reverse {
+            -self.update_settings.time
+        } else {
+            self.update_settings.time
+        };
+        let (iterations, integrator) =
+            (self.update_settings.iterations, self.update_settings.integrator);
+        match self.update_settings.adaptive {
+            Some(ref adaptive) => {
+                self.universe
+                    .update_state_adaptive(time, iterations, adaptive, integrator, &self.forces)
+            }
+            None => {
+                self.universe.update_state_repeat(time, iterations, integrator, &self.forces)
+            }
         }
-        // continue
-        IterationResult::Ok
+    }
+
+    // hands the ui the new state, the trails aren't copied
+    fn publish(&self) {
+        self.latest_state.put(self.generation, self.universe.clone());
+    }
+}
+
+fn sleep(seconds: f64) {
+    if seconds > 0. {
+        let nanos = (seconds.fract() * 1e9) as u32;
+        ::std::thread::sleep(Duration::new(seconds.trunc() as u64, nanos));
     }
 }
 
@@ -100,14 +229,23 @@ pub enum UpdaterCommand {
     UpdateSettings(UpdateSettings),
     Pause,
     Unpause,
-    SetFpsUpdateTime(f64),
-    SetUniverse(ColorUniverse),
-    UniverseConsumed,
+    SetRate(SimRate),
+    // states made from older generations are ignored by the ui
+    SetUniverse(ColorUniverse, usize),
 }
 
+#[derive(Clone)]
 pub struct UpdateSettings {
     time: f64,
     iterations: usize,
+    // None keeps the number of iterations fixed
+    adaptive: Option<AdaptiveTimestep>,
+    integrator: Integrator,
+    forces: ForceMode,
+    // threads the forces are worked out on
+    threads: usize,
+    // same results whatever the number of threads
+    deterministic: bool,
 }
 
 impl Default for UpdateSettings {
@@ -115,12 +253,118 @@ impl Default for UpdateSettings {
         UpdateSettings {
             time: 3000.,
             iterations: 100,
+            adaptive: None,
+            integrator: Integrator::default(),
+            forces: ForceMode::default(),
+            threads: 1,
+            deterministic: true,
         }
     }
 }
 
+// splits each step into as many iterations as the closest encounter needs
+#[derive(Clone, Debug)]
+pub struct AdaptiveTimestep {
+    // fraction of the shortest time scale one iteration may take
+    pub accuracy: f64,
+    // how many times shorter or longer than the fixed dt an iteration may get
+    pub range: f64,
+}
+
+impl Default for AdaptiveTimestep {
+    fn default() -> AdaptiveTimestep {
+        AdaptiveTimestep {
+            accuracy: 0.02,
+            range: 30.,
+        }
+    }
+}
+
+impl AdaptiveTimestep {
+    // bounds around the dt the step settings would use without adapting
+    pub fn min_dt(&self, fixed_dt: f64) -> f64 {
+        fixed_dt / self.range
+    }
+
+    pub fn max_dt(&self, fixed_dt: f64) -> f64 {
+        fixed_dt * self.range
+    }
+
+    pub fn dt(&self, fixed_dt: f64, time_scale: Option<f64>) -> f64 {
+        let dt = match time_scale {
+            Some(time_scale) => self.accuracy * time_scale,
+            None => self.max_dt(fixed_dt),
+        };
+        dt.max(self.min_dt(fixed_dt)).min(self.max_dt(fixed_dt))
+    }
+}
+
 impl UpdateSettings {
     pub fn time(&self) -> f64 {
         self.time
     }
+
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    // length of an iteration when it isn't adaptive
+    pub fn fixed_dt(&self) -> f64 {
+        self.time / self.iterations as f64
+    }
+
+    pub fn integrator(&self) -> Integrator {
+        self.integrator
+    }
+
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+    }
+
+    pub fn forces(&self) -> ForceMode {
+        self.forces
+    }
+
+    // the library integrator has its own forces, so barnes-hut switches away from it
+    // returns whether it did
+    pub fn set_forces(&mut self, forces: ForceMode) -> bool {
+        self.forces = forces;
+        if let (ForceMode::BarnesHut { .. }, Integrator::Library) = (forces, self.integrator) {
+            self.integrator = Integrator::Leapfrog;
+            return true;
+        }
+        false
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1).min(MAX_THREADS);
+    }
+
+    pub fn deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    pub fn adaptive(&self) -> Option<&AdaptiveTimestep> {
+        self.adaptive.as_ref()
+    }
+
+    pub fn toggle_adaptive(&mut self) {
+        self.adaptive = match self.adaptive {
+            Some(_) => None,
+            None => Some(AdaptiveTimestep::default()),
+        };
+    }
+
+    // one step for every frame drawn
+    pub fn default_rate(&self) -> f64 {
+        self.time * DEFAULT_FPS
+    }
 }